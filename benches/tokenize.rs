@@ -0,0 +1,90 @@
+use std::time::Instant;
+
+use xylo::{
+    analyzer::syntax,
+    program::Program,
+    token::Token,
+    tokenizer::tokenize,
+    utils::{Interner, Symbol},
+};
+
+// Tokenizes and validates a large generated module, run with `cargo bench`. Finding the keyword of
+// every form is also timed both with interned symbols and with the string compares identifiers
+// needed before they were interned. Token trees were not moved into an arena, see Token, so what
+// interning saves is what this shows.
+const DEFINITIONS: usize = 20000;
+const RUNS: u32 = 5;
+
+fn main() {
+    let mut source = String::new();
+    for i in 0..DEFINITIONS {
+        source.push_str(&format!("#| Adds things\n{{function add-{i} [Int, Int -> Int] {{a b}} {{+ a {{* b {{- a 1}}}}}}}}\n"));
+        source.push_str(&format!("{{let value-{i} [Int] {{add-{i} 1 {{fun {{x}} {{max x 2}}}}}}}}\n"));
+    }
+
+    let text = source.as_bytes();
+    let mut tokenizing = 0.0;
+    let mut validating = 0.0;
+    let mut matching_symbols = 0.0;
+    let mut matching_strings = 0.0;
+
+    for _ in 0..RUNS {
+        let mut program = Program::new();
+        let file = program.add_source("bench.xl".to_string(), text.to_vec());
+
+        let start = Instant::now();
+        let tokens = tokenize(text, file, program.get_interner_mut()).unwrap();
+        tokenizing += start.elapsed().as_secs_f64();
+
+        let start = Instant::now();
+        let n_symbols = count_keywords(&tokens, |t| t.match_first_identifier(Symbol::LET));
+        matching_symbols += start.elapsed().as_secs_f64();
+
+        let interner = program.get_interner();
+        let start = Instant::now();
+        let n_strings = count_keywords(&tokens, |t| first_identifier_is(t, "let", interner));
+        matching_strings += start.elapsed().as_secs_f64();
+
+        assert_eq!(n_symbols, n_strings);
+
+        program.new_module("Bench".to_string(), tokens);
+
+        let start = Instant::now();
+        let errors = syntax::validate(&mut program);
+        validating += start.elapsed().as_secs_f64();
+
+        assert!(errors.is_empty());
+    }
+
+    let ms = |total: f64| total * 1000.0 / RUNS as f64;
+
+    println!("{} definitions, {} bytes", DEFINITIONS * 2, text.len());
+    println!("tokenize:    {:8.2} ms", ms(tokenizing));
+    println!("validate:    {:8.2} ms", ms(validating));
+    println!("match let, symbols: {:8.2} ms", ms(matching_symbols));
+    println!("match let, strings: {:8.2} ms", ms(matching_strings));
+}
+
+// Every list in the tree that starts with the keyword, looked at the way the syntax pass does
+fn count_keywords(tokens: &[Token], matches: impl Fn(&Token) -> bool + Copy) -> usize {
+    let mut n = 0;
+
+    for token in tokens {
+        if matches(token) {
+            n += 1;
+        }
+
+        if let Some(list) = token.sexpr() {
+            n += count_keywords(list, matches);
+        }
+    }
+
+    return n;
+}
+
+fn first_identifier_is(token: &Token, keyword: &str, interner: &Interner) -> bool {
+    token.sexpr()
+        .and_then(|list| list.first())
+        .and_then(|first| first.identifier())
+        .is_some_and(|name| interner.resolve(name) == keyword)
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::*,
+    program::*,
+    utils::*,
+    analyzer::{
+        lower::lower_expr,
+        resolve::{lookup_name, resolve_local, Resolved},
+        utils::unquoted,
+    },
+};
+
+// Top level values are initialised before main runs, in an order where everything a value needs
+// is already there. A value needs the values it names and, when it calls a function or procedure,
+// everything that function needs in turn. Lambdas only run once they are called so whatever their
+// body names isn't needed yet, that is what lets functions be mutually recursive.
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Visiting,
+    Done,
+}
+
+struct Graph {
+    // What every value, function and procedure needs when it is initialised or called
+    edges: HashMap<Global, Vec<Global>>,
+    is_value: HashMap<Global, bool>,
+}
+
+// Everything top level definitions name, validate adds every definition as it lowers it so the
+// lowering is shared with the other passes that read it
+pub struct Dependencies {
+    // Whether a function is needed depends on if it is called, so everything named is kept until
+    // every definition is known
+    named: HashMap<Global, Vec<(Global, bool)>>,
+    is_value: HashMap<Global, bool>,
+}
+
+impl Dependencies {
+    pub fn new() -> Self {
+        Self {
+            named: HashMap::new(),
+            is_value: HashMap::new(),
+        }
+    }
+
+    // Top level expressions and anything that isn't a value, function or procedure are skipped
+    pub fn add(&mut self, program: &Program, module: usize, decl: &Decl, errors: &mut Vec<Error>) {
+        let mut needs = Vec::new();
+
+        let is_value = match decl {
+            Decl::Let { value, .. } => {
+                collect_needs(value, false, program, module, &[], &mut needs, errors);
+                true
+            },
+
+            Decl::Function { params, body, .. } => {
+                let locals: Vec<Symbol> = params.iter().map(|p| p.name.name).collect();
+                for expr in body {
+                    collect_needs(expr, false, program, module, &locals, &mut needs, errors);
+                }
+                false
+            },
+
+            Decl::Procedure { body, .. } => {
+                for expr in body {
+                    collect_needs(expr, false, program, module, &[], &mut needs, errors);
+                }
+                false
+            },
+
+            _ => return,
+        };
+
+        let global = Global { module, name: decl.name().unwrap().name };
+
+        self.named.insert(global, needs);
+        self.is_value.insert(global, is_value);
+    }
+}
+
+// Order to initialise every top level value of the program in, values that end up needing
+// themselves are reported with the whole chain of definitions that leads back to them
+pub fn initialization_order(program: &Program, dependencies: Dependencies) -> (Vec<Global>, Vec<Error>) {
+    let mut errors = Vec::new();
+    let graph = build_graph(dependencies);
+
+    let mut order = Vec::new();
+    let mut states = HashMap::new();
+
+    for module in program.get_modules() {
+        for global in values_in_order(module) {
+            visit(global, &graph, program, &mut states, &mut order, &mut errors);
+        }
+    }
+
+    return (order, errors);
+}
+
+// Values of a module in the order they are written, so independent values keep that order
+fn values_in_order(module: &Module) -> Vec<Global> {
+    let mut values: Vec<(usize, Symbol)> = module.variables.iter()
+        .map(|(name, index)| (*index, *name))
+        .collect();
+
+    values.sort();
+
+    return values.into_iter()
+        .map(|(_, name)| Global { module: module.id, name })
+        .collect();
+}
+
+fn build_graph(dependencies: Dependencies) -> Graph {
+    let Dependencies { named, is_value } = dependencies;
+    let mut edges = HashMap::new();
+
+    for (global, needs) in named {
+        let mut global_edges: Vec<Global> = Vec::new();
+
+        // Naming a function or procedure without calling it doesn't run it, what isn't in the graph
+        // couldn't be lowered
+        for (need, called) in needs {
+            let need_is_value = match is_value.get(&need) {
+                Some(is_value) => *is_value,
+                None => continue,
+            };
+
+            if (called || need_is_value) && !global_edges.contains(&need) {
+                global_edges.push(need);
+            }
+        }
+
+        edges.insert(global, global_edges);
+    }
+
+    return Graph { edges, is_value };
+}
+
+// Top level definitions named in expr and whether they are called, parameters shadow top level
+// names and anything that can't be resolved is left for the passes that report it
+fn collect_needs(expr: &Expr, called: bool, program: &Program, module: usize, locals: &[Symbol], needs: &mut Vec<(Global, bool)>, errors: &mut Vec<Error>) {
+    match expr {
+        Expr::Call { function, args, .. } => {
+            collect_needs(function, true, program, module, locals, needs, errors);
+
+            for arg in args {
+                collect_needs(&arg.value, false, program, module, locals, needs, errors);
+            }
+        },
+
+        Expr::Identifier(_) | Expr::Path(..) => {
+            if let Some(global) = resolve_global(expr, program, module, locals) {
+                needs.push((global, called));
+            }
+        },
+
+        Expr::Sweet(exprs, _) => {
+            for expr in exprs {
+                collect_needs(expr, false, program, module, locals, needs, errors);
+            }
+        },
+
+        Expr::Quote { quasi: true, token, .. } => {
+            for token in unquoted(&**token) {
+                match lower_expr(token) {
+                    Ok(expr) => collect_needs(&expr, false, program, module, locals, needs, errors),
+                    Err(e) => errors.push(e),
+                }
+            }
+        },
+
+        Expr::Lambda { .. } | Expr::Quote { .. } | Expr::Literal(..) => {},
+    }
+}
+
+// Top level let or procedure that a name or path refers to
+fn resolve_global(expr: &Expr, program: &Program, module: usize, locals: &[Symbol]) -> Option<Global> {
+    let resolved = match expr {
+        Expr::Identifier(name) if locals.contains(&name.name) => return None,
+        Expr::Identifier(name) => lookup_name(program, module, name.name, name.si),
+        Expr::Path(path, _) => resolve_local(program, module, path),
+        _ => return None,
+    };
+
+    let (module, name) = match resolved {
+        Ok(Resolved::Definition { module, name }) => (module, name),
+        _ => return None,
+    };
+
+    let m = program.get_module_by_id(module).unwrap();
+
+    if !m.variables.contains_key(&name) && !m.procedures.contains_key(&name) {
+        return None;
+    }
+
+    return Some(Global { module, name });
+}
+
+// Depth first, a value is only added to the order once everything it needs has been. The path
+// from root is kept on a stack instead of recursing so long chains of values can't overflow it.
+fn visit(root: Global, graph: &Graph, program: &Program, states: &mut HashMap<Global, State>, order: &mut Vec<Global>, errors: &mut Vec<Error>) {
+    if states.contains_key(&root) {
+        return;
+    }
+
+    // Every definition on the path with how many of its needs have been visited
+    let mut path: Vec<(Global, usize)> = vec![(root, 0)];
+    states.insert(root, State::Visiting);
+
+    while let Some((global, n_visited)) = path.last().copied() {
+        let needs = graph.edges.get(&global).map_or(&[][..], |needs| needs.as_slice());
+
+        let need = match needs.get(n_visited) {
+            Some(need) => *need,
+            None => {
+                path.pop();
+                states.insert(global, State::Done);
+
+                if is_value(graph, global) {
+                    order.push(global);
+                }
+
+                continue;
+            },
+        };
+
+        path.last_mut().unwrap().1 += 1;
+
+        match states.get(&need) {
+            Some(State::Done) => {},
+
+            Some(State::Visiting) => {
+                let start = path.iter().position(|(g, _)| *g == need).unwrap();
+                let cycle: Vec<Global> = path[start..].iter().map(|(g, _)| *g).collect();
+
+                // Functions calling each other is just recursion, it only matters when a value is in it
+                if let Some(value) = cycle.iter().find(|g| is_value(graph, **g)) {
+                    errors.push(cycle_error(&cycle, *value, program));
+                }
+            },
+
+            None => {
+                states.insert(need, State::Visiting);
+                path.push((need, 0));
+            },
+        }
+    }
+}
+
+fn is_value(graph: &Graph, global: Global) -> bool {
+    return graph.is_value.get(&global).copied().unwrap_or(false);
+}
+
+// a -> f -> b -> a, written from the first value in the cycle and pointing at its name
+fn cycle_error(cycle: &[Global], value: Global, program: &Program) -> Error {
+    let interner = program.get_interner();
+    let start = cycle.iter().position(|g| *g == value).unwrap();
+
+    let names: Vec<String> = cycle[start..].iter()
+        .chain(cycle[..start].iter())
+        .chain(std::iter::once(&value))
+        .map(|g| {
+            let name = interner.resolve(g.name);
+
+            if g.module == value.module {
+                return name.to_string();
+            }
+
+            return format!("{}.{name}", program.get_module_by_id(g.module).unwrap().name);
+        })
+        .collect();
+
+    let m = program.get_module_by_id(value.module).unwrap();
+    let index = m.variables[&value.name];
+
+    return Error {
+        message: format!("'{}' can't be initialised since it needs itself, {}", interner.resolve(value.name), names.join(" -> ")),
+        si: m.code[index].sexpr().unwrap()[1].si,
+    };
+}
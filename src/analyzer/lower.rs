@@ -0,0 +1,343 @@
+use crate::{
+    ast::*,
+    program::*,
+    token::*,
+    utils::{Error, Symbol},
+    analyzer::utils::*,
+};
+
+// Turns validated token trees into the ast, the syntax pass has already made sure every form has
+// the shape that is expected here. A token that still isn't an expression where one is expected
+// is returned as an error.
+//
+// Macro expansion, validation, sweet expressions and variadic calls rewrite code so they work on
+// tokens. Only once code is what it will stay is it lowered, name resolution and initialisation
+// order read the ast that lower_module gives them.
+
+// Values, procedures and expressions of a module in the order they are written, lowered one at a
+// time as they are read. Definitions with errors were never added to the module so they are left
+// out.
+pub fn lower_module(module: &Module) -> impl Iterator<Item = Result<Decl, Error>> + '_ {
+    let mut indices: Vec<usize> = module.variables.values()
+        .chain(module.procedures.values())
+        .chain(module.expressions.iter())
+        .copied()
+        .collect();
+
+    indices.sort();
+
+    return indices.into_iter().map(|index| lower_decl(&module.code[index]));
+}
+
+pub fn lower_decl(token: &Token) -> Result<Decl, Error> {
+    if is_variable(token) {
+        return lower_let(token);
+    }
+
+    if is_procedure(token) {
+        return lower_procedure(token);
+    }
+
+    if is_struct(token) {
+        return Ok(lower_struct(token));
+    }
+
+    if is_enum(token) {
+        return Ok(lower_enum(token));
+    }
+
+    if is_import(token) {
+        return Ok(lower_import(token));
+    }
+
+    if is_infix(token) {
+        return Ok(lower_infix(token));
+    }
+
+    if is_macro(token) {
+        return Ok(lower_macro(token));
+    }
+
+    return Ok(Decl::Expr(lower_expr(token)?));
+}
+
+// {let name type value}, a lambda as the value makes it a function
+fn lower_let(token: &Token) -> Result<Decl, Error> {
+    let sexpr = token.sexpr().unwrap();
+
+    let name = lower_name(&sexpr[1]);
+    let t = lower_type(&sexpr[2]);
+    let value = &sexpr[3];
+
+    if is_lambda(value) {
+        let lambda = value.sexpr().unwrap();
+
+        return Ok(Decl::Function {
+            name, t,
+            params: lower_params(&lambda[1]),
+            body: lower_body(&lambda[2..])?,
+            doc: token.doc.clone(),
+            si: token.si,
+        });
+    }
+
+    return Ok(Decl::Let {
+        name, t,
+        value: lower_expr(value)?,
+        doc: token.doc.clone(),
+        si: token.si,
+    });
+}
+
+// {procedure name type body...}
+fn lower_procedure(token: &Token) -> Result<Decl, Error> {
+    let sexpr = token.sexpr().unwrap();
+
+    return Ok(Decl::Procedure {
+        name: lower_name(&sexpr[1]),
+        t: lower_type(&sexpr[2]),
+        body: lower_body(&sexpr[3..])?,
+        doc: token.doc.clone(),
+        si: token.si,
+    });
+}
+
+// {struct Name {where ...} {field Type}...}
+fn lower_struct(token: &Token) -> Decl {
+    let sexpr = token.sexpr().unwrap();
+
+    let (params, fields) = match sexpr.get(2) {
+        Some(t) if t.match_first_identifier(Symbol::WHERE) => (lower_where(t), &sexpr[3..]),
+        _ => (Vec::new(), &sexpr[2..]),
+    };
+
+    let fields = fields.iter()
+        .map(|field| {
+            let field_sexpr = field.sexpr().unwrap();
+
+            Field {
+                name: lower_name(&field_sexpr[0]),
+                t: lower_type(&field_sexpr[1]),
+                si: field.si,
+            }
+        })
+        .collect();
+
+    return Decl::Struct {
+        name: lower_name(&sexpr[1]),
+        params, fields,
+        doc: token.doc.clone(),
+        si: token.si,
+    };
+}
+
+// {enum Name {where ...} {Variant Type...}...}
+fn lower_enum(token: &Token) -> Decl {
+    let sexpr = token.sexpr().unwrap();
+
+    let (params, variants) = match sexpr.get(2) {
+        Some(t) if t.match_first_identifier(Symbol::WHERE) => (lower_where(t), &sexpr[3..]),
+        _ => (Vec::new(), &sexpr[2..]),
+    };
+
+    let variants = variants.iter()
+        .map(|variant| {
+            let variant_sexpr = variant.sexpr().unwrap();
+
+            Variant {
+                name: lower_name(&variant_sexpr[0]),
+                fields: variant_sexpr[1..].iter().map(lower_type).collect(),
+                si: variant.si,
+            }
+        })
+        .collect();
+
+    return Decl::Enum {
+        name: lower_name(&sexpr[1]),
+        params, variants,
+        doc: token.doc.clone(),
+        si: token.si,
+    };
+}
+
+// {import Module Alias {include name...}}, everything after the module name is optional
+fn lower_import(token: &Token) -> Decl {
+    let sexpr = token.sexpr().unwrap();
+
+    let module = match &sexpr[1].kind {
+        TokenKind::Path(path) => path.clone(),
+        _ => vec![PathSegment { name: sexpr[1].identifier().unwrap(), si: sexpr[1].si }],
+    };
+
+    let mut rest = &sexpr[2..];
+    let mut alias = None;
+
+    if let Some(name) = rest.first().filter(|t| t.is_identifier()) {
+        alias = Some(lower_name(name));
+        rest = &rest[1..];
+    }
+
+    let filter = match rest.first() {
+        Some(t) if t.match_first_identifier(Symbol::INCLUDE) => ImportFilter::Include(t.sexpr().unwrap()[1..].iter().map(lower_name).collect()),
+        Some(t) => ImportFilter::Exclude(t.sexpr().unwrap()[1..].iter().map(lower_name).collect()),
+        None => ImportFilter::All,
+    };
+
+    return Decl::Import {
+        module,
+        qualified: token.match_first_identifier(Symbol::IMPORT_QUALIFIED),
+        alias, filter,
+        si: token.si,
+    };
+}
+
+// {infixl operator precedence function}, the operator is its own function if there is none
+fn lower_infix(token: &Token) -> Decl {
+    let sexpr = token.sexpr().unwrap();
+
+    let associativity = if token.match_first_identifier(Symbol::INFIXL) {
+        Associativity::Left
+    } else if token.match_first_identifier(Symbol::INFIXR) {
+        Associativity::Right
+    } else {
+        Associativity::None
+    };
+
+    let precedence = match sexpr[2].kind {
+        TokenKind::UInt(precedence, _) => precedence as u8,
+        _ => 0,
+    };
+
+    let operator = lower_name(&sexpr[1]);
+
+    return Decl::Infix {
+        operator, associativity, precedence,
+        function: sexpr.get(3).map_or(operator, lower_name),
+        si: token.si,
+    };
+}
+
+fn lower_macro(token: &Token) -> Decl {
+    let sexpr = token.sexpr().unwrap();
+
+    return Decl::Macro {
+        name: lower_name(&sexpr[1]),
+        params: lower_params(&sexpr[2]),
+        body: sexpr[3..].to_vec(),
+        doc: token.doc.clone(),
+        si: token.si,
+    };
+}
+
+// {where a {b Num Eq}}
+fn lower_where(token: &Token) -> Vec<TypeParam> {
+    token.sexpr().unwrap()[1..].iter()
+        .map(|entry| match entry.sexpr() {
+            Some(list) => TypeParam {
+                name: lower_name(&list[0]),
+                traits: list[1..].iter().map(lower_name).collect(),
+            },
+
+            None => TypeParam {
+                name: lower_name(entry),
+                traits: Vec::new(),
+            },
+        })
+        .collect()
+}
+
+pub fn lower_expr(token: &Token) -> Result<Expr, Error> {
+    let si = token.si;
+
+    let expr = match &token.kind {
+        TokenKind::SExpr(sexpr) if sexpr.is_empty() => Expr::Literal(Literal::Unit, si),
+
+        TokenKind::SExpr(sexpr) if is_lambda(token) => Expr::Lambda {
+            params: lower_params(&sexpr[1]),
+            body: lower_body(&sexpr[2..])?,
+            si,
+        },
+
+        TokenKind::SExpr(sexpr) if token.match_first_identifier(Symbol::QUOTE) || token.match_first_identifier(Symbol::QUASIQUOTE) => Expr::Quote {
+            quasi: token.match_first_identifier(Symbol::QUASIQUOTE),
+            token: Box::new(sexpr[1].clone()),
+            si,
+        },
+
+        TokenKind::SExpr(sexpr) => Expr::Call {
+            function: Box::new(lower_expr(&sexpr[0])?),
+            args: lower_args(&sexpr[1..])?,
+            si,
+        },
+
+        TokenKind::SweetExpr(sweet) => Expr::Sweet(lower_body(sweet)?, si),
+
+        TokenKind::Identifier(name) => Expr::Identifier(Name { name: *name, si }),
+        TokenKind::Path(path) => Expr::Path(path.clone(), si),
+
+        TokenKind::UInt(v, t) => Expr::Literal(Literal::UInt(*v, *t), si),
+        TokenKind::Float(v, t) => Expr::Literal(Literal::Float(*v, *t), si),
+        TokenKind::String(v) => Expr::Literal(Literal::String(v.clone()), si),
+        TokenKind::ByteString(v) => Expr::Literal(Literal::ByteString(v.clone()), si),
+        TokenKind::Char(v) => Expr::Literal(Literal::Char(*v), si),
+        TokenKind::Byte(v) => Expr::Literal(Literal::Byte(*v), si),
+
+        TokenKind::TypeExpr(_) | TokenKind::Variadic(_) | TokenKind::Label(_) => return Err(Error {
+            message: "Expected an expression".to_string(),
+            si,
+        }),
+    };
+
+    return Ok(expr);
+}
+
+fn lower_body(tokens: &[Token]) -> Result<Vec<Expr>, Error> {
+    tokens.iter().map(lower_expr).collect()
+}
+
+// Labels are always followed by their value
+fn lower_args(tokens: &[Token]) -> Result<Vec<Arg>, Error> {
+    let mut args = Vec::new();
+    let mut tokens = tokens.iter();
+
+    while let Some(token) = tokens.next() {
+        let arg = match &token.kind {
+            TokenKind::Label(label) => Arg {
+                label: Some(Name { name: *label, si: token.si }),
+                value: lower_expr(tokens.next().unwrap())?,
+            },
+
+            _ => Arg {
+                label: None,
+                value: lower_expr(token)?,
+            },
+        };
+
+        args.push(arg);
+    }
+
+    return Ok(args);
+}
+
+fn lower_params(params: &Token) -> Vec<Param> {
+    params.sexpr().unwrap().iter()
+        .map(|p| Param {
+            name: Name { name: p.identifier_or_variadic().unwrap(), si: p.si },
+            variadic: p.is_variadic(),
+        })
+        .collect()
+}
+
+fn lower_name(token: &Token) -> Name {
+    Name {
+        name: token.identifier().unwrap(),
+        si: token.si,
+    }
+}
+
+fn lower_type(token: &Token) -> Type {
+    match &token.kind {
+        TokenKind::TypeExpr(t) => t.clone(),
+        _ => Type::Unknown,
+    }
+}
@@ -0,0 +1,556 @@
+use std::{
+    collections::HashMap,
+    rc::Rc,
+};
+
+use crate::{
+    program::*,
+    token::*,
+    utils::*,
+    analyzer::utils::{code_children, is_macro, is_quotation, lambda_params},
+};
+
+// {macro unless {condition body} `{if ,condition {} ,body}}
+// Macros run at compile time on the code they are called with and whatever they return is put in
+// place of the call before anything is validated. While a macro runs it can only use its
+// parameters, quote code and call other macros or one of the builtins below, never the code it
+// is expanding into.
+//
+// Expansion is hygienic, parameters of lambdas a macro writes into its expansion are renamed so
+// they can't capture names from the code it was called with. The other way around, a name the
+// macro writes into its expansion that a parameter around the call would capture is reported.
+
+// TODO: Macros are only visible in the module they are defined in, imports are collected later
+
+// Macros that keep expanding into calls to themselves, directly or somewhere inside of what they
+// expand into, are stopped at this depth
+const MAX_DEPTH: usize = 256;
+
+// Functions on code that can be called while a macro runs
+const BUILTINS: [&str; 4] = ["list", "first", "rest", "cons"];
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<Symbol>,
+    variadic: bool,
+    // Shared so a call doesn't copy the whole body
+    body: Rc<[Token]>,
+
+    // A macro that calls code it isn't allowed to is reported once where it is defined and calls
+    // to it are left alone
+    is_valid: bool,
+}
+
+// Expands every macro call in every module, see validate
+pub fn expand_macros(program: &mut Program) -> Vec<Error> {
+    let mut errors = Vec::new();
+    let (modules, interner) = program.get_modules_mut_with_interner_mut();
+
+    for module in modules {
+        for (index, token) in module.code.iter().enumerate() {
+            if is_well_formed(token) {
+                // A macro defined twice is reported when the module is validated, the first one stays
+                module.macros.entry(token.sexpr().unwrap()[1].identifier().unwrap()).or_insert(index);
+            }
+        }
+
+        let macros = collect_macros(&module.code, interner, &mut errors);
+        expand(&mut module.code, macros, interner, &mut errors);
+    }
+
+    return errors;
+}
+
+// For repl use, macros from earlier inputs are in previous
+pub fn expand_code(code: &mut [Token], previous: &[Token], interner: &mut Interner) -> Vec<Error> {
+    let mut errors = Vec::new();
+
+    let mut macros = collect_macros(previous, interner, &mut errors);
+    macros.extend(collect_macros(code, interner, &mut errors));
+
+    expand(code, macros, interner, &mut errors);
+    return errors;
+}
+
+// Malformed macros are reported by validate_macro
+fn is_well_formed(token: &Token) -> bool {
+    if !is_macro(token) {
+        return false;
+    }
+
+    let sexpr = token.sexpr().unwrap();
+
+    let has_name = sexpr.get(1).is_some_and(|t| t.is_identifier());
+    let has_params = sexpr.get(2).and_then(|t| t.sexpr()).is_some_and(|params| {
+        params.iter().all(|p| p.is_identifier() || p.is_variadic()) &&
+        params.iter().position(|p| p.is_variadic()).is_none_or(|i| i == params.len() - 1)
+    });
+
+    return has_name && has_params && sexpr.len() > 3;
+}
+
+fn collect_macros(code: &[Token], interner: &Interner, errors: &mut Vec<Error>) -> HashMap<Symbol, Macro> {
+    let mut macros = HashMap::new();
+
+    for token in code.iter().filter(|t| is_well_formed(t)) {
+        let sexpr = token.sexpr().unwrap();
+        let params = sexpr[2].sexpr().unwrap();
+
+        macros.entry(sexpr[1].identifier().unwrap()).or_insert(Macro {
+            params: params.iter().map(|p| p.identifier_or_variadic().unwrap()).collect(),
+            variadic: params.last().is_some_and(|p| p.is_variadic()),
+            body: sexpr[3..].into(),
+            is_valid: true,
+        });
+    }
+
+    // Macros can call each other so they are only checked once they are all known
+    let names: Vec<Symbol> = macros.keys().copied().collect();
+    for name in names {
+        let m = &macros[&name];
+        let n_errors = errors.len();
+
+        for token in m.body.iter() {
+            check_body(token, &m.params, &macros, interner, errors);
+        }
+
+        if errors.len() != n_errors {
+            macros.get_mut(&name).unwrap().is_valid = false;
+        }
+    }
+
+    return macros;
+}
+
+// What runs when a macro is called, quoted code is only data and doesn't have to follow the rules
+fn check_body(token: &Token, params: &[Symbol], macros: &HashMap<Symbol, Macro>, interner: &Interner, errors: &mut Vec<Error>) {
+    if is_quotation(token) {
+        for token in code_children(token) {
+            check_body(token, params, macros, interner, errors);
+        }
+
+        return;
+    }
+
+    match &token.kind {
+        TokenKind::Identifier(name) if !params.contains(name) => errors.push(Error {
+            message: format!("Macros can only use their parameters and call other macros, '{}' is neither", interner.resolve(*name)),
+            si: token.si,
+        }),
+
+        TokenKind::SExpr(list) if !list.is_empty() => {
+            match list[0].identifier() {
+                Some(name) if macros.contains_key(&name) || BUILTINS.contains(&interner.resolve(name)) => {},
+                Some(name) => errors.push(Error {
+                    message: format!("Macros can only call other macros, '{}' is not a macro", interner.resolve(name)),
+                    si: list[0].si,
+                }),
+
+                None => errors.push(Error {
+                    message: "Macros can only call other macros".to_string(),
+                    si: list[0].si,
+                }),
+            }
+
+            for token in &list[1..] {
+                check_body(token, params, macros, interner, errors);
+            }
+        },
+
+        TokenKind::Path(path) => errors.push(Error {
+            message: format!("Macros can only use their parameters and call other macros, '{}' is neither", path_to_string(path, interner)),
+            si: token.si,
+        }),
+
+        TokenKind::SweetExpr(_) | TokenKind::Label(_) | TokenKind::Variadic(_) => errors.push(Error {
+            message: "This can't be run while a macro runs, quote it to put it in the expansion".to_string(),
+            si: token.si,
+        }),
+
+        _ => {},
+    }
+}
+
+fn expand(code: &mut [Token], macros: HashMap<Symbol, Macro>, interner: &mut Interner, errors: &mut Vec<Error>) {
+    if macros.is_empty() {
+        return;
+    }
+
+    let mut expander = Expander {
+        macros,
+        interner,
+        n_renamed: 0,
+        depth: 0,
+        introduced: Vec::new(),
+    };
+
+    for token in code.iter_mut().filter(|t| !is_macro(t)) {
+        expander.expand_token(token, 0, &mut Vec::new(), errors);
+    }
+}
+
+struct Expander<'a> {
+    macros: HashMap<Symbol, Macro>,
+    interner: &'a mut Interner,
+
+    // Used to make every renamed name unique
+    n_renamed: usize,
+
+    // How many macro calls deep the one running is
+    depth: usize,
+
+    // Names the templates of the last expansion wrote into it, not the ones from its arguments
+    introduced: Vec<Symbol>,
+}
+
+type Env = HashMap<Symbol, Token>;
+
+impl<'a> Expander<'a> {
+    // Depth counts the expansions token came out of, including the ones of the tokens around it
+    fn expand_token(&mut self, token: &mut Token, mut depth: usize, shadowed: &mut Vec<Symbol>, errors: &mut Vec<Error>) {
+        // What a macro expands into can be an other macro call
+        while let Some(name) = self.macro_call(token, shadowed) {
+            if depth == MAX_DEPTH {
+                errors.push(Error {
+                    message: format!("Macro expansion went {MAX_DEPTH} calls deep, '{}' probably keeps expanding into itself", self.interner.resolve(name)),
+                    si: token.si,
+                });
+                return;
+            }
+
+            let args = token.sexpr().unwrap()[1..].to_vec();
+            self.introduced.clear();
+
+            match self.call(name, args, token.si) {
+                Ok(_) if self.introduced.iter().any(|n| shadowed.contains(n)) => {
+                    let captured = *self.introduced.iter().find(|n| shadowed.contains(n)).unwrap();
+                    let captured = self.interner.resolve(captured);
+
+                    errors.push(Error {
+                        message: format!("Macro '{}' uses '{captured}' which a parameter named '{captured}' around the call would capture, rename the parameter", self.interner.resolve(name)),
+                        si: token.si,
+                    });
+                    return;
+                },
+
+                Ok(expansion) => {
+                    // Docs written above the call document what it expands into
+                    let doc = token.doc.take();
+                    *token = expansion.with_doc(doc);
+                },
+
+                Err(e) => {
+                    errors.push(e);
+                    return;
+                },
+            }
+
+            depth += 1;
+        }
+
+        let n_shadowed = shadowed.len();
+        shadowed.extend(lambda_params(token));
+
+        for token in code_children(&mut *token) {
+            self.expand_token(token, depth, shadowed, errors);
+        }
+
+        shadowed.truncate(n_shadowed);
+    }
+
+    fn macro_call(&self, token: &Token, shadowed: &[Symbol]) -> Option<Symbol> {
+        let name = token.sexpr()?.first()?.identifier()?;
+
+        if shadowed.contains(&name) || !self.macros.get(&name)?.is_valid {
+            return None;
+        }
+
+        return Some(name);
+    }
+
+    fn call(&mut self, name: Symbol, mut args: Vec<Token>, si: SourceInfo) -> Result<Token, Error> {
+        let m = self.macros[&name].clone();
+
+        // Its errors are reported where it is defined, a valid macro can still call it
+        if !m.is_valid {
+            return Err(Error {
+                message: format!("Macro '{}' can't be expanded since it has errors", self.interner.resolve(name)),
+                si,
+            });
+        }
+        let n_fixed = m.params.len() - m.variadic as usize;
+
+        if args.len() < n_fixed || (!m.variadic && args.len() > n_fixed) {
+            let at_least = if m.variadic { "at least " } else { "" };
+
+            return Err(Error {
+                message: format!("Macro '{}' takes {at_least}{n_fixed} arguments but {} were given", self.interner.resolve(name), args.len()),
+                si,
+            });
+        }
+
+        if self.depth == MAX_DEPTH {
+            return Err(Error {
+                message: format!("Macro expansion went {MAX_DEPTH} calls deep, '{}' probably keeps expanding into itself", self.interner.resolve(name)),
+                si,
+            });
+        }
+
+        let mut env = Env::new();
+
+        if m.variadic {
+            let rest = args.split_off(n_fixed);
+            env.insert(*m.params.last().unwrap(), Token::new(TokenKind::SExpr(rest), si));
+        }
+
+        env.extend(m.params.iter().copied().zip(args));
+
+        self.depth += 1;
+        let result = self.eval_body(&m.body, &env);
+        self.depth -= 1;
+
+        // The expansion takes the place of the call
+        let mut result = result?;
+        result.si = si;
+
+        return Ok(result);
+    }
+
+    fn eval_body(&mut self, body: &[Token], env: &Env) -> Result<Token, Error> {
+        let mut result = None;
+
+        for token in body {
+            result = Some(self.eval(token, env)?);
+        }
+
+        return Ok(result.unwrap());
+    }
+
+    // check_body has made sure this only meets code a macro can run
+    fn eval(&mut self, token: &Token, env: &Env) -> Result<Token, Error> {
+        let list = match &token.kind {
+            TokenKind::Identifier(name) => return match env.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(Error {
+                    message: format!("'{}' is not a parameter of the macro", self.interner.resolve(*name)),
+                    si: token.si,
+                }),
+            },
+            TokenKind::SExpr(list) if !list.is_empty() => list,
+            _ => return Ok(token.clone()),
+        };
+
+        if is_quotation(token) {
+            if list.len() != 2 {
+                return Err(Error {
+                    message: format!("'{}' takes exactly one expression", self.interner.resolve(list[0].identifier().unwrap())),
+                    si: token.si,
+                });
+            }
+
+            if token.match_first_identifier(Symbol::QUOTE) {
+                return Ok(list[1].clone());
+            }
+
+            if token.match_first_identifier(Symbol::QUASIQUOTE) {
+                let mut renames = HashMap::new();
+                self.find_binders(&list[1], 1, &mut renames);
+
+                return self.instantiate(&list[1], 1, env, &renames);
+            }
+
+            return Err(Error {
+                message: format!("'{}' can only be used inside of a quasiquote", self.interner.resolve(list[0].identifier().unwrap())),
+                si: token.si,
+            });
+        }
+
+        let name = list[0].identifier().unwrap();
+
+        let mut args = Vec::new();
+        for arg in &list[1..] {
+            args.push(self.eval(arg, env)?);
+        }
+
+        if self.macros.contains_key(&name) {
+            return self.call(name, args, token.si);
+        }
+
+        return self.builtin(name, args, token.si);
+    }
+
+    fn builtin(&mut self, name: Symbol, args: Vec<Token>, si: SourceInfo) -> Result<Token, Error> {
+        let builtin = self.interner.resolve(name).to_string();
+
+        let n_params = match builtin.as_str() {
+            "list" => args.len(),
+            "cons" => 2,
+            _ => 1,
+        };
+
+        if args.len() != n_params {
+            return Err(Error {
+                message: format!("'{builtin}' takes {n_params} arguments but {} were given", args.len()),
+                si,
+            });
+        }
+
+        if builtin == "list" {
+            return Ok(Token::new(TokenKind::SExpr(args), si));
+        }
+
+        let mut args = args.into_iter();
+        let first = args.next().unwrap();
+
+        if builtin == "cons" {
+            let mut list = expect_list(&builtin, args.next().unwrap())?;
+            list.insert(0, first);
+
+            return Ok(Token::new(TokenKind::SExpr(list), si));
+        }
+
+        let arg_si = first.si;
+        let mut list = expect_list(&builtin, first)?;
+
+        if list.is_empty() {
+            return Err(Error {
+                message: format!("'{builtin}' needs a list with something in it"),
+                si: arg_si,
+            });
+        }
+
+        if builtin == "first" {
+            return Ok(list.swap_remove(0));
+        }
+
+        list.remove(0);
+        return Ok(Token::new(TokenKind::SExpr(list), si));
+    }
+
+    // Copies a quasiquoted template, unquoted parts are evaluated and ',@' splices a list into the
+    // list around it. Depth counts the quasiquotes around token, only depth 1 unquotes run.
+    fn instantiate(&mut self, token: &Token, depth: usize, env: &Env, renames: &HashMap<Symbol, Symbol>) -> Result<Token, Error> {
+        let is_unquote = token.match_first_identifier(Symbol::UNQUOTE) || token.match_first_identifier(Symbol::UNQUOTE_SPLICING);
+
+        if is_unquote && depth == 1 {
+            if token.match_first_identifier(Symbol::UNQUOTE_SPLICING) {
+                return Err(Error {
+                    message: "',@' can only be used inside of a list".to_string(),
+                    si: token.si,
+                });
+            }
+
+            return self.eval_unquoted(token, env);
+        }
+
+        let depth = if token.match_first_identifier(Symbol::QUASIQUOTE) {
+            depth + 1
+        } else if is_unquote {
+            depth - 1
+        } else {
+            depth
+        };
+
+        let n_introduced = self.introduced.len();
+
+        let kind = match &token.kind {
+            TokenKind::SExpr(list) => TokenKind::SExpr(self.instantiate_list(list, depth, env, renames)?),
+            TokenKind::SweetExpr(list) => TokenKind::SweetExpr(self.instantiate_list(list, depth, env, renames)?),
+
+            TokenKind::Identifier(name) => match renames.get(name) {
+                Some(renamed) => TokenKind::Identifier(*renamed),
+                None => {
+                    self.introduced.push(*name);
+                    TokenKind::Identifier(*name)
+                },
+            },
+
+            TokenKind::Variadic(name) => TokenKind::Variadic(*renames.get(name).unwrap_or(name)),
+
+            kind => kind.clone(),
+        };
+
+        // Names in quoted data don't refer to anything
+        if token.match_first_identifier(Symbol::QUOTE) {
+            self.introduced.truncate(n_introduced);
+        }
+
+        return Ok(Token::new(kind, token.si));
+    }
+
+    fn instantiate_list(&mut self, list: &[Token], depth: usize, env: &Env, renames: &HashMap<Symbol, Symbol>) -> Result<Vec<Token>, Error> {
+        let mut result = Vec::with_capacity(list.len());
+
+        for token in list {
+            if depth == 1 && token.match_first_identifier(Symbol::UNQUOTE_SPLICING) {
+                let spliced = self.eval_unquoted(token, env)?;
+                result.extend(expect_list("unquote-splicing", spliced)?);
+            } else {
+                result.push(self.instantiate(token, depth, env, renames)?);
+            }
+        }
+
+        return Ok(result);
+    }
+
+    // The x in {unquote x}
+    fn eval_unquoted(&mut self, unquote: &Token, env: &Env) -> Result<Token, Error> {
+        match unquote.sexpr().unwrap() {
+            [_, expr] => self.eval(expr, env),
+            list => Err(Error {
+                message: format!("'{}' takes exactly one expression", self.interner.resolve(list[0].identifier().unwrap())),
+                si: unquote.si,
+            }),
+        }
+    }
+
+    // Parameters of lambdas written in the template itself get a name nothing else can have
+    fn find_binders(&mut self, token: &Token, depth: usize, renames: &mut HashMap<Symbol, Symbol>) {
+        let is_unquote = token.match_first_identifier(Symbol::UNQUOTE) || token.match_first_identifier(Symbol::UNQUOTE_SPLICING);
+
+        if is_unquote && depth == 1 {
+            return;
+        }
+
+        if token.match_first_identifier(Symbol::FUN) {
+            let params = token.sexpr().unwrap().get(1).and_then(|p| p.sexpr()).unwrap_or(&[]);
+
+            for name in params.iter().filter_map(|p| p.identifier_or_variadic()) {
+                renames.entry(name).or_insert_with(|| self.fresh(name));
+            }
+        }
+
+        let depth = if token.match_first_identifier(Symbol::QUASIQUOTE) {
+            depth + 1
+        } else if is_unquote {
+            depth - 1
+        } else {
+            depth
+        };
+
+        if let TokenKind::SExpr(list) | TokenKind::SweetExpr(list) = &token.kind {
+            for token in list {
+                self.find_binders(token, depth, renames);
+            }
+        }
+    }
+
+    // '#' starts a comment so no name written in code can have one in it, that way the renamed
+    // name can't be captured
+    fn fresh(&mut self, name: Symbol) -> Symbol {
+        self.n_renamed += 1;
+
+        let fresh = format!("{}#{}", self.interner.resolve(name), self.n_renamed);
+        return self.interner.intern(&fresh);
+    }
+}
+
+fn expect_list(builtin: &str, token: Token) -> Result<Vec<Token>, Error> {
+    match token.kind {
+        TokenKind::SExpr(list) => Ok(list),
+        _ => Err(Error {
+            message: format!("'{builtin}' needs a list"),
+            si: token.si,
+        }),
+    }
+}
@@ -1,2 +1,6 @@
 pub mod syntax;
 pub mod utils;
+pub mod resolve;
+pub mod lower;
+pub mod macros;
+pub mod init_order;
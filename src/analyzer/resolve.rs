@@ -0,0 +1,475 @@
+use crate::{
+    ast::*,
+    program::*,
+    token::*,
+    utils::*,
+    analyzer::{
+        lower::lower_expr,
+        utils::unquoted,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolved {
+    Module(usize),
+
+    Definition {
+        module: usize,
+        name: Symbol,
+    },
+
+    // Enums are only types, structs are also their constructor
+    Type {
+        module: usize,
+        name: Symbol,
+    },
+
+    // Module.My-Struct.field1 or Module.My-Maybe.Some
+    Member {
+        module: usize,
+        owner: Symbol,
+        name: Symbol,
+    },
+}
+
+impl Resolved {
+    // Module the name is in, or the module itself
+    pub fn module(&self) -> usize {
+        match self {
+            Resolved::Module(id) => *id,
+            Resolved::Definition { module, .. } => *module,
+            Resolved::Type { module, .. } => *module,
+            Resolved::Member { module, .. } => *module,
+        }
+    }
+}
+
+// Resolves a qualified name one segment at a time, module names can have dots in them so the
+// longest module name that matches the start of the path is used.
+// Errors point at the first segment that could not be resolved.
+pub fn resolve_path(program: &Program, path: &[PathSegment]) -> Result<Resolved, Error> {
+    let interner = program.get_interner();
+    let mut module_name = String::new();
+    let mut module = None;
+
+    for (i, segment) in path.iter().enumerate() {
+        if i > 0 {
+            module_name.push('.');
+        }
+
+        module_name.push_str(interner.resolve(segment.name));
+
+        if let Some(id) = program.get_module_id(&module_name) {
+            module = Some(id);
+            continue;
+        }
+
+        let is_module_prefix = program.get_modules().iter()
+            .any(|m| m.name.starts_with(&module_name) && m.name[module_name.len()..].starts_with('.'));
+
+        if is_module_prefix {
+            continue;
+        }
+
+        let id = match module {
+            Some(id) => id,
+            None => return Err(Error::new(
+                format!("Unknown module '{module_name}'"),
+                segment.si,
+            )),
+        };
+
+        return resolve_in_module(program, id, &path[i..]);
+    }
+
+    return match module {
+        Some(id) => Ok(Resolved::Module(id)),
+        None => Err(Error::new(
+            format!("Unknown module '{module_name}'"),
+            path.last().unwrap().si,
+        )),
+    };
+}
+
+// Names written inside of a module. Its own definitions come first, then qualified names through
+// the name or alias of a module it imports and last unqualified names from import. My-Maybe.Some
+// can be used without a module in front of it wherever My-Maybe can.
+pub fn resolve_local(program: &Program, module: usize, path: &[PathSegment]) -> Result<Resolved, Error> {
+    let interner = program.get_interner();
+    let m = program.get_module_by_id(module).unwrap();
+
+    if m.has_definition(path[0].name) || m.has_type(path[0].name) {
+        return resolve_in_module(program, module, path);
+    }
+
+    let mut prefix = String::new();
+    let mut hidden = None;
+
+    for (i, segment) in path.iter().enumerate() {
+        if i > 0 {
+            prefix.push('.');
+        }
+
+        prefix.push_str(interner.resolve(segment.name));
+
+        if prefix == m.name {
+            return match path.get(i + 1) {
+                Some(_) => resolve_in_module(program, module, &path[i + 1..]),
+                None => Ok(Resolved::Module(module)),
+            };
+        }
+
+        for import in m.imports.iter().filter(|import| import.prefix(interner) == prefix) {
+            let id = match program.get_module_id(&import.module) {
+                Some(id) => id,
+                None => return Err(Error::new(
+                    format!("Unknown module '{}'", import.module),
+                    segment.si,
+                )),
+            };
+
+            let name = match path.get(i + 1) {
+                Some(name) => name,
+                None => return Ok(Resolved::Module(id)),
+            };
+
+            // An other import of the same module might still allow it
+            if !import.allows(name.name) {
+                hidden = Some(Error::new(
+                    format!("'{}' is not imported from module '{}'", interner.resolve(name.name), import.module),
+                    name.si,
+                ));
+                continue;
+            }
+
+            return resolve_in_module(program, id, &path[i + 1..]);
+        }
+    }
+
+    if let Some(error) = hidden {
+        return Err(error);
+    }
+
+    // Std.Console.println where Std.Console is a module of the program that isn't imported here
+    if path.len() > 1 {
+        if let Ok(resolved) = resolve_path(program, path) {
+            return Err(Error::new(
+                format!("Module '{}' is not imported", program.get_module_by_id(resolved.module()).unwrap().name),
+                path[0].si,
+            ));
+        }
+    }
+
+    if let Some(id) = find_unqualified(program, m, &path[0])? {
+        return resolve_in_module(program, id, path);
+    }
+
+    // Defined in an imported module but not brought in without its module in front of it
+    if let Some(import) = m.imports.iter().find(|import| defines(program, &import.module, path[0].name)) {
+        let message = if import.qualified {
+            format!("'{}' is only imported qualified from module '{}'", interner.resolve(path[0].name), import.module)
+        } else {
+            format!("'{}' is not imported from module '{}'", interner.resolve(path[0].name), import.module)
+        };
+
+        return Err(Error::new(message, path[0].si));
+    }
+
+    return Err(Error::new(
+        format!("'{}' is not defined or imported", interner.resolve(path[0].name)),
+        path[0].si,
+    ));
+}
+
+// Whether a module of the program defines name, a module that doesn't exist defines nothing
+fn defines(program: &Program, module: &String, name: Symbol) -> bool {
+    return program.get_module_by_name(module).is_some_and(|m| m.has_definition(name) || m.has_type(name));
+}
+
+// Whether any module that module imports defines name, whether or not the import lets it through
+fn is_imported_name(program: &Program, module: usize, name: Symbol) -> bool {
+    let m = program.get_module_by_id(module).unwrap();
+    return m.imports.iter().any(|import| defines(program, &import.module, name));
+}
+
+// A name without anything in front of it
+pub fn lookup_name(program: &Program, module: usize, name: Symbol, si: SourceInfo) -> Result<Resolved, Error> {
+    return resolve_local(program, module, &[PathSegment { name, si }]);
+}
+
+// Module that one of the unqualified imports brings name in from, two different modules bringing
+// in the same name is an error
+fn find_unqualified(program: &Program, m: &Module, name: &PathSegment) -> Result<Option<usize>, Error> {
+    let interner = program.get_interner();
+    let mut found: Option<usize> = None;
+
+    for import in m.imports.iter().filter(|import| !import.qualified && import.allows(name.name)) {
+        let id = match program.get_module_id(&import.module) {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let target = program.get_module_by_id(id).unwrap();
+        if !target.has_definition(name.name) && !target.has_type(name.name) {
+            continue;
+        }
+
+        match found {
+            Some(first) if first != id => return Err(Error::new(
+                format!("'{}' is ambiguous, it is imported from both '{}' and '{}'", interner.resolve(name.name), program.get_module_by_id(first).unwrap().name, target.name),
+                name.si,
+            )),
+
+            _ => found = Some(id),
+        }
+    }
+
+    return Ok(found);
+}
+
+// Imported modules have to exist and everything they include or exclude has to be defined in them
+pub fn check_imports(program: &Program) -> Vec<Error> {
+    let interner = program.get_interner();
+    let mut errors = Vec::new();
+
+    for m in program.get_modules() {
+        for import in &m.imports {
+            let target = match program.get_module_by_name(&import.module) {
+                Some(target) => target,
+                None => {
+                    errors.push(Error::new(
+                        format!("Unknown module '{}'", import.module),
+                        import.si,
+                    ));
+                    continue;
+                },
+            };
+
+            if target.id == m.id {
+                errors.push(Error::new(
+                    format!("Module '{}' can't import itself", m.name),
+                    import.si,
+                ));
+                continue;
+            }
+
+            let names = match &import.filter {
+                ImportFilter::All => continue,
+                ImportFilter::Include(names) | ImportFilter::Exclude(names) => names,
+            };
+
+            for name in names {
+                if !target.has_definition(name.name) && !target.has_type(name.name) {
+                    errors.push(Error::new(
+                        format!("'{}' is not defined in module '{}'", interner.resolve(name.name), target.name),
+                        name.si,
+                    ));
+                }
+            }
+        }
+    }
+
+    return errors;
+}
+
+// Every qualified name and every name an import is about in a top level definition or expression
+// has to resolve to something
+pub fn check_names(program: &Program, module: usize, decl: &Decl, errors: &mut Vec<Error>) {
+    let (mut locals, body) = match decl {
+        Decl::Let { value, .. } | Decl::Expr(value) => (Vec::new(), std::slice::from_ref(value)),
+        Decl::Function { params, body, .. } => (params.iter().map(|p| p.name.name).collect(), body.as_slice()),
+        Decl::Procedure { body, .. } => (Vec::new(), body.as_slice()),
+        _ => return,
+    };
+
+    for expr in body {
+        check_expr(expr, program, module, &mut locals, errors);
+    }
+}
+
+// Parameters of the lambdas expr is in are in locals, they hide top level names
+fn check_expr(expr: &Expr, program: &Program, module: usize, locals: &mut Vec<Symbol>, errors: &mut Vec<Error>) {
+    match expr {
+        Expr::Call { function, args, .. } => {
+            check_expr(function, program, module, locals, errors);
+
+            for arg in args {
+                check_expr(&arg.value, program, module, locals, errors);
+            }
+        },
+
+        Expr::Lambda { params, body, .. } => {
+            let n_locals = locals.len();
+            locals.extend(params.iter().map(|p| p.name.name));
+
+            for expr in body {
+                check_expr(expr, program, module, locals, errors);
+            }
+
+            locals.truncate(n_locals);
+        },
+
+        Expr::Identifier(name) if locals.contains(&name.name) => {},
+
+        // There is no prelude yet so a name nothing in the program defines could still be a
+        // builtin like + or println, only names that an import is about are checked
+        Expr::Identifier(name) if program.get_module_by_id(module).unwrap().has_definition(name.name) => {},
+
+        Expr::Identifier(name) => {
+            if !is_imported_name(program, module, name.name) {
+                return;
+            }
+
+            if let Err(e) = lookup_name(program, module, name.name, name.si) {
+                errors.push(e);
+            }
+        },
+
+        Expr::Path(path, _) => {
+            if let Err(e) = resolve_local(program, module, path) {
+                errors.push(e);
+            }
+        },
+
+        // Lowering can't fail on what validation let through in a quasiquote
+        Expr::Quote { quasi: true, token, .. } => {
+            for expr in unquoted(&**token).into_iter().filter_map(|t| lower_expr(t).ok()) {
+                check_expr(&expr, program, module, locals, errors);
+            }
+        },
+
+        // (a + b) that is still a sweet expression couldn't be desugared and already has an error
+        Expr::Sweet(..) | Expr::Quote { .. } | Expr::Literal(..) => {},
+    }
+}
+
+// The rest of a path after the module it is in, a definition or type and maybe one of its members
+fn resolve_in_module(program: &Program, id: usize, path: &[PathSegment]) -> Result<Resolved, Error> {
+    let interner = program.get_interner();
+    let m = program.get_module_by_id(id).unwrap();
+    let segment = &path[0];
+
+    if !m.has_definition(segment.name) && !m.has_type(segment.name) {
+        return Err(Error::new(
+            format!("'{}' is not defined in module '{}'", interner.resolve(segment.name), m.name),
+            segment.si,
+        ));
+    }
+
+    if let Some(next) = path.get(1) {
+        if !m.has_member(segment.name, next.name) {
+            return Err(Error::new(
+                format!("'{}' in module '{}' has no member '{}'", interner.resolve(segment.name), m.name, interner.resolve(next.name)),
+                next.si,
+            ));
+        }
+
+        if let Some(rest) = path.get(2) {
+            return Err(Error::new(
+                format!("'{}.{}' in module '{}' has no member '{}'", interner.resolve(segment.name), interner.resolve(next.name), m.name, interner.resolve(rest.name)),
+                rest.si,
+            ));
+        }
+
+        return Ok(Resolved::Member {
+            module: id,
+            owner: segment.name,
+            name: next.name,
+        });
+    }
+
+    if !m.has_definition(segment.name) {
+        return Ok(Resolved::Type {
+            module: id,
+            name: segment.name,
+        });
+    }
+
+    return Ok(Resolved::Definition {
+        module: id,
+        name: segment.name,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        program::Program,
+        tokenizer::tokenize,
+        analyzer::syntax::validate,
+    };
+
+    const LIB: (&str, &str) = ("Lib", "{let one 1} {let two 2} {enum Color {Red} {Green}}");
+
+    // Line, column and message of every error in a program made of the modules, each module is
+    // its own file
+    fn errors(modules: &[(&str, &str)]) -> Vec<(i64, i64, String)> {
+        let mut program = Program::new();
+
+        for (name, text) in modules {
+            let file = program.add_source(format!("{name}.xl"), text.as_bytes().to_vec());
+            let code = tokenize(text.as_bytes(), file, program.get_interner_mut()).unwrap();
+            program.new_module(name.to_string(), code);
+        }
+
+        return validate(&mut program).into_iter().map(|e| (e.si.line, e.si.column, e.message)).collect();
+    }
+
+    // Errors of a Main module next to LIB
+    fn main_errors(text: &str) -> Vec<(i64, i64, String)> {
+        errors(&[LIB, ("Main", text)])
+    }
+
+    fn error(column: i64, message: &str) -> (i64, i64, String) {
+        (1, column, message.to_string())
+    }
+
+    #[test]
+    fn imported_names() {
+        assert_eq!(main_errors("{import Lib} {let x {+ one Lib.two}}"), []);
+        assert_eq!(main_errors("{import Lib} {let c Color.Red} {let d Lib.Color.Green}"), []);
+        assert_eq!(main_errors("{import Lib {include one}} {let x {+ one Lib.one}}"), []);
+        assert_eq!(main_errors("{import-qualified Lib L} {let x L.one}"), []);
+
+        // Builtins and names that are neither imported nor paths are left for later passes
+        assert_eq!(main_errors("{let x {+ 1 2}} {procedure main [Unit] {println x}}"), []);
+
+        // Parameters hide imported names
+        assert_eq!(main_errors("{import Lib {include two}} {let f {fun {one} one}}"), []);
+    }
+
+    #[test]
+    fn import_errors() {
+        let cases = [
+            ("{import Lib {include one}} {let x two}", 35, "'two' is not imported from module 'Lib'"),
+            ("{import Lib {exclude one}} {let x one}", 35, "'one' is not imported from module 'Lib'"),
+
+            // Include and exclude apply to qualified names too
+            ("{import Lib {exclude one}} {let x Lib.one}", 39, "'one' is not imported from module 'Lib'"),
+            ("{import-qualified Lib} {let x one}", 31, "'one' is only imported qualified from module 'Lib'"),
+            ("{import-qualified Lib L} {let x Lib.one}", 33, "Module 'Lib' is not imported"),
+            ("{let x Lib.one}", 8, "Module 'Lib' is not imported"),
+            ("{import Lib} {let x Lib.three}", 25, "'three' is not defined in module 'Lib'"),
+            ("{import Lib} {let x Color.Blue}", 27, "'Color' in module 'Lib' has no member 'Blue'"),
+            ("{import Nope}", 9, "Unknown module 'Nope'"),
+            ("{import Main}", 9, "Module 'Main' can't import itself"),
+            ("{import Lib {include three}}", 22, "'three' is not defined in module 'Lib'"),
+        ];
+
+        for (text, column, message) in cases {
+            assert_eq!(main_errors(text), [error(column, message)], "{text}");
+        }
+    }
+
+    #[test]
+    fn ambiguous_imports() {
+        let other = ("Other", "{let one 1}");
+
+        assert_eq!(errors(&[LIB, other, ("Main", "{import Lib} {import Other} {let x one}")]), [
+            error(36, "'one' is ambiguous, it is imported from both 'Lib' and 'Other'"),
+        ]);
+
+        // Leaving it out of one of the imports settles it
+        assert_eq!(errors(&[LIB, other, ("Main", "{import Lib} {import Other {exclude one}} {let x one}")]), []);
+    }
+}
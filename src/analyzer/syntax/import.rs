@@ -0,0 +1,134 @@
+use crate::{
+    ast::*,
+    program::*,
+    token::*,
+    utils::*,
+    analyzer::lower::lower_decl,
+};
+
+// {import Std.Console}, {import Std.Console {include println}} or {import-qualified Std.Network Net}
+// An alias can follow the module name and include or exclude can come after that
+pub fn validate_import(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = sexpr.si;
+    let sexpr = sexpr.sexpr().unwrap();
+    let keyword = interner.resolve(sexpr[0].identifier().unwrap());
+
+    if !sexpr.get(1).is_some_and(|t| t.is_identifier() || t.is_path()) {
+        errors.push(Error {
+            message: format!("'{keyword}' requires the name of a module"),
+            si,
+        });
+        return;
+    }
+
+    let mut rest = &sexpr[2..];
+    if rest.first().is_some_and(|t| t.is_identifier()) {
+        rest = &rest[1..];
+    }
+
+    let mut filter: Option<&Token> = None;
+
+    for token in rest {
+        let is_include = token.match_first_identifier(Symbol::INCLUDE);
+
+        if !is_include && !token.match_first_identifier(Symbol::EXCLUDE) {
+            errors.push(Error {
+                message: "Expected an alias, {include name...} or {exclude name...} after the module name".to_string(),
+                si: token.si,
+            });
+            continue;
+        }
+
+        if let Some(first) = filter {
+            let message = if first.match_first_identifier(Symbol::INCLUDE) == is_include {
+                format!("'{}' can only be used once in an import", if is_include { "include" } else { "exclude" })
+            } else {
+                format!("An import can't both include and exclude names, the first one was at {}:{}", first.si.line, first.si.column)
+            };
+
+            errors.push(Error {
+                message,
+                si: token.si,
+            });
+            continue;
+        }
+
+        let names = &token.sexpr().unwrap()[1..];
+
+        if names.is_empty() {
+            errors.push(Error {
+                message: "Include and exclude require at least one name".to_string(),
+                si: token.si,
+            });
+        }
+
+        for name in names.iter().filter(|n| !n.is_identifier()) {
+            errors.push(Error {
+                message: "Only names can be included or excluded".to_string(),
+                si: name.si,
+            });
+        }
+
+        filter = Some(token);
+    }
+}
+
+// Records the import at code[index] on the module, the imported module doesn't have to exist yet
+pub fn define_import(module: &mut Module, index: usize, interner: &Interner) {
+    if let Ok(Decl::Import { module: path, qualified, alias, filter, .. }) = lower_decl(&module.code[index]) {
+        module.imports.push(Import {
+            module: path_to_string(&path, interner),
+            qualified,
+            alias: alias.map(|a| a.name),
+            filter,
+            si: path.first().unwrap().si.to(path.last().unwrap().si),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tokenizer::tokenize, analyzer::syntax::validate};
+
+    // Column and message of every error in Main, Lib is there to be imported
+    fn errors(text: &str) -> Vec<(i64, String)> {
+        let mut program = Program::new();
+
+        for (name, text) in [("Lib", "{let one 1} {let two 2}"), ("Main", text)] {
+            let file = program.add_source(format!("{name}.xl"), text.as_bytes().to_vec());
+            let code = tokenize(text.as_bytes(), file, program.get_interner_mut()).unwrap();
+            program.new_module(name.to_string(), code);
+        }
+
+        return validate(&mut program).into_iter().map(|e| (e.si.column, e.message)).collect();
+    }
+
+    #[test]
+    fn imports() {
+        assert_eq!(errors("{import Lib}"), []);
+        assert_eq!(errors("{import Lib L {include one two}}"), []);
+        assert_eq!(errors("{import-qualified Lib {exclude two}}"), []);
+    }
+
+    #[test]
+    fn import_syntax_errors() {
+        const NOT_AN_ALIAS: &str = "Expected an alias, {include name...} or {exclude name...} after the module name";
+
+        let cases = [
+            ("{import}", 1, "'import' requires the name of a module"),
+            ("{import-qualified \"Lib\"}", 1, "'import-qualified' requires the name of a module"),
+            ("{import Lib \"L\"}", 13, NOT_AN_ALIAS),
+            ("{import Lib L M}", 15, NOT_AN_ALIAS),
+            ("{import Lib {include one} {include two}}", 27, "'include' can only be used once in an import"),
+            ("{import Lib {include one} {exclude two}}", 27,
+                "An import can't both include and exclude names, the first one was at 1:13"),
+            ("{import Lib {include}}", 13, "Include and exclude require at least one name"),
+            ("{import Lib {exclude \"one\"}}", 22, "Only names can be included or excluded"),
+        ];
+
+        for (text, column, message) in cases {
+            assert_eq!(errors(text), [(column, message.to_string())], "{text}");
+        }
+    }
+}
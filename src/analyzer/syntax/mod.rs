@@ -1,6 +1,18 @@
 mod validate;
 pub use validate::*;
 
+mod variadic;
+pub use variadic::*;
+
+mod types;
+pub use types::*;
+
+mod import;
+pub use import::*;
+
+mod sweet;
+pub use sweet::*;
+
 // We need to be able to execute top level macros at some point
 // Also have some way to do partial validation to a module for parts that have not been expanded
 // yet
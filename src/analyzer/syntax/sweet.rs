@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::*,
+    program::*,
+    token::*,
+    utils::*,
+    analyzer::{
+        lower::lower_decl,
+        utils::code_children,
+    },
+};
+
+// Functions used in infix position without a declaration, like (a mod b)
+const DEFAULT_OPERATOR: (Associativity, u8) = (Associativity::Left, 9);
+
+// {infixl + 5 infix-add}, {infixr ++ 5} or {infix == 4}
+// Without a function the operator calls the function with its own name
+pub fn validate_infix(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = sexpr.si;
+    let sexpr = sexpr.sexpr().unwrap();
+    let keyword = interner.resolve(sexpr[0].identifier().unwrap());
+
+    if !sexpr.get(1).is_some_and(|t| t.is_identifier()) {
+        errors.push(Error {
+            message: format!("'{keyword}' requires an operator"),
+            si,
+        });
+        return;
+    }
+
+    match sexpr.get(2).map(|t| &t.kind) {
+        Some(TokenKind::UInt(precedence, None)) if *precedence <= 9 => {},
+        Some(_) => errors.push(Error {
+            message: "Precedence has to be a number from 0 to 9".to_string(),
+            si: sexpr[2].si,
+        }),
+
+        None => errors.push(Error {
+            message: format!("'{keyword}' requires a precedence"),
+            si,
+        }),
+    }
+
+    if let Some(function) = sexpr.get(3).filter(|t| !t.is_identifier()) {
+        errors.push(Error {
+            message: "Operators can only stand for a function name".to_string(),
+            si: function.si,
+        });
+    }
+
+    if let Some(extra) = sexpr.get(4) {
+        errors.push(Error {
+            message: format!("'{keyword}' takes an operator, a precedence and a function"),
+            si: extra.si,
+        });
+    }
+}
+
+// Records the infix declaration at code[index] on the module
+pub fn define_operator(module: &mut Module, index: usize) {
+    if let Ok(Decl::Infix { operator, associativity, precedence, function, si }) = lower_decl(&module.code[index]) {
+        module.operators.insert(operator.name, Operator {
+            associativity,
+            precedence,
+            function: function.name,
+            si,
+        });
+    }
+}
+
+// Operators a module can use, its own and the ones its unqualified imports bring in
+pub fn visible_operators(program: &Program, module: usize) -> HashMap<Symbol, Operator> {
+    let m = program.get_module_by_id(module).unwrap();
+    let mut operators = HashMap::new();
+
+    for import in m.imports.iter().filter(|import| !import.qualified) {
+        let target = match program.get_module_by_name(&import.module) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        for (name, operator) in &target.operators {
+            if import.allows(*name) {
+                operators.insert(*name, *operator);
+            }
+        }
+    }
+
+    operators.extend(m.operators.iter().map(|(name, operator)| (*name, *operator)));
+    return operators;
+}
+
+// Turns every sweet expression in code into prefix calls, (a + b * c) becomes
+// {+ a {* b c}} if '*' binds tighter than '+'
+pub fn desugar_sweet_exprs(code: &mut [Token], operators: &HashMap<Symbol, Operator>, interner: &Interner, errors: &mut Vec<Error>) {
+    for token in code {
+        desugar_token(token, operators, interner, errors);
+    }
+}
+
+fn desugar_token(token: &mut Token, operators: &HashMap<Symbol, Operator>, interner: &Interner, errors: &mut Vec<Error>) {
+    for token in code_children(&mut *token) {
+        desugar_token(token, operators, interner, errors);
+    }
+
+    if let TokenKind::SweetExpr(sweet) = &mut token.kind {
+        if is_well_formed(sweet, token.si, errors) {
+            let sweet = std::mem::take(sweet);
+            *token = desugar(sweet, token.si, operators, interner, errors);
+        }
+    }
+}
+
+// Operands with a name between every two of them
+fn is_well_formed(sweet: &[Token], si: SourceInfo, errors: &mut Vec<Error>) -> bool {
+    if sweet.is_empty() {
+        errors.push(Error {
+            message: "Sweet expressions can't be empty".to_string(),
+            si,
+        });
+        return false;
+    }
+
+    if sweet.len().is_multiple_of(2) {
+        errors.push(Error {
+            message: "Sweet expressions have to alternate between operands and operators, like (a + b)".to_string(),
+            si,
+        });
+        return false;
+    }
+
+    let n_errors = errors.len();
+
+    for operator in sweet.iter().skip(1).step_by(2).filter(|t| !t.is_identifier()) {
+        errors.push(Error {
+            message: "Only names can be used as operators".to_string(),
+            si: operator.si,
+        });
+    }
+
+    return errors.len() == n_errors;
+}
+
+// Shunting yard over operands and the operators between them. An operator on the stack is applied
+// before the next one if it binds tighter, or just as tight and both are left associative.
+fn desugar(sweet: Vec<Token>, si: SourceInfo, operators: &HashMap<Symbol, Operator>, interner: &Interner, errors: &mut Vec<Error>) -> Token {
+    let mut operands: Vec<Token> = Vec::new();
+    let mut stack: Vec<(Token, Operator)> = Vec::new();
+
+    for (i, token) in sweet.into_iter().enumerate() {
+        if i % 2 == 0 {
+            operands.push(token);
+            continue;
+        }
+
+        let name = token.identifier().unwrap();
+
+        let operator = operators.get(&name).copied().unwrap_or(Operator {
+            associativity: DEFAULT_OPERATOR.0,
+            precedence: DEFAULT_OPERATOR.1,
+            function: name,
+            si: token.si,
+        });
+
+        while let Some((top_token, top)) = stack.last() {
+            if top.precedence < operator.precedence {
+                break;
+            }
+
+            if top.precedence == operator.precedence {
+                match (top.associativity, operator.associativity) {
+                    (Associativity::Left, Associativity::Left) => {},
+                    (Associativity::Right, Associativity::Right) => break,
+
+                    _ => errors.push(Error {
+                        message: mixing_error(top_token, top, &token, &operator, interner),
+                        si: token.si,
+                    }),
+                }
+            }
+
+            apply(&mut operands, &mut stack);
+        }
+
+        stack.push((token, operator));
+    }
+
+    while !stack.is_empty() {
+        apply(&mut operands, &mut stack);
+    }
+
+    let mut result = operands.pop().unwrap();
+
+    // The call covers the parentheses too
+    result.si = si;
+    return result;
+}
+
+fn apply(operands: &mut Vec<Token>, stack: &mut Vec<(Token, Operator)>) {
+    let (token, operator) = stack.pop().unwrap();
+    let rhs = operands.pop().unwrap();
+    let lhs = operands.pop().unwrap();
+
+    let si = lhs.si.to(rhs.si);
+    let function = Token::new(TokenKind::Identifier(operator.function), token.si);
+
+    operands.push(Token::new(TokenKind::SExpr(vec![function, lhs, rhs]), si));
+}
+
+fn mixing_error(first_token: &Token, first: &Operator, second_token: &Token, second: &Operator, interner: &Interner) -> String {
+    let first_name = interner.resolve(first_token.identifier().unwrap());
+    let second_name = interner.resolve(second_token.identifier().unwrap());
+
+    if first.associativity == Associativity::None && first_name == second_name {
+        return format!("'{first_name}' is non-associative, use parentheses to use it more than once");
+    }
+
+    if first.associativity == Associativity::None || second.associativity == Associativity::None {
+        return format!("'{first_name}' and '{second_name}' have the same precedence and one of them is non-associative, use parentheses to mix them");
+    }
+
+    return format!("'{first_name}' and '{second_name}' have the same precedence but associate in different directions, use parentheses to mix them");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tokenizer::tokenize, analyzer::syntax::validate};
+
+    const OPERATORS: &str = "{infixl + 6} {infixl - 6} {infixl * 7} {infixr ^ 8} {infix == 4}
+                             {infixl <> 6 append}";
+
+    // Values of every let in the last module written as s-expressions, and the column and
+    // message of every error
+    fn desugared(modules: &[(&str, &str)]) -> (Vec<String>, Vec<(i64, String)>) {
+        let mut program = Program::new();
+
+        for (name, text) in modules {
+            let file = program.add_source(format!("{name}.xl"), text.as_bytes().to_vec());
+            let code = tokenize(text.as_bytes(), file, program.get_interner_mut()).unwrap();
+            program.new_module(name.to_string(), code);
+        }
+
+        let errors = validate(&mut program).into_iter().map(|e| (e.si.column, e.message)).collect();
+
+        let interner = program.get_interner();
+        let values = program.get_modules().last().unwrap().code.iter()
+            .filter(|t| t.match_first_identifier(Symbol::LET))
+            .map(|t| write(t.sexpr().unwrap().last().unwrap(), interner))
+            .collect();
+
+        return (values, errors);
+    }
+
+    fn write(token: &Token, interner: &Interner) -> String {
+        match &token.kind {
+            TokenKind::SExpr(list) => {
+                let list: Vec<String> = list.iter().map(|t| write(t, interner)).collect();
+                format!("{{{}}}", list.join(" "))
+            },
+            TokenKind::Identifier(name) => interner.resolve(*name).to_string(),
+            TokenKind::UInt(value, None) => value.to_string(),
+            kind => format!("{kind:?}"),
+        }
+    }
+
+    fn desugar_one(expr: &str) -> String {
+        let (values, errors) = desugared(&[("Main", &format!("{OPERATORS} {{let x {expr}}}"))]);
+
+        assert_eq!(errors, [], "{expr}");
+        return values.into_iter().next().unwrap();
+    }
+
+    // Column of each error in expr and its message
+    fn sweet_errors(operators: &str, expr: &str) -> Vec<(i64, String)> {
+        let (_, errors) = desugared(&[("Main", &format!("{operators}\n{{let x {expr}}}"))]);
+        return errors.into_iter().map(|(column, message)| (column - 7, message)).collect();
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(desugar_one("(a + b * c)"), "{+ a {* b c}}");
+        assert_eq!(desugar_one("(a * b + c)"), "{+ {* a b} c}");
+        assert_eq!(desugar_one("(a + b == c * d)"), "{== {+ a b} {* c d}}");
+        assert_eq!(desugar_one("(a * (b + c))"), "{* a {+ b c}}");
+        assert_eq!(desugar_one("({f 1} + 2)"), "{+ {f 1} 2}");
+    }
+
+    #[test]
+    fn associativity() {
+        assert_eq!(desugar_one("(a - b - c)"), "{- {- a b} c}");
+        assert_eq!(desugar_one("(a ^ b ^ c)"), "{^ a {^ b c}}");
+        assert_eq!(desugar_one("(a + b - c + d)"), "{+ {- {+ a b} c} d}");
+    }
+
+    #[test]
+    fn operator_functions() {
+        assert_eq!(desugar_one("(a <> b + c)"), "{+ {append a b} c}");
+
+        // Undeclared operators are left associative and bind tighter than any declared one
+        assert_eq!(desugar_one("(a mod b * c)"), "{* {mod a b} c}");
+        assert_eq!(desugar_one("(a * b mod c)"), "{* a {mod b c}}");
+    }
+
+    #[test]
+    fn imported_operators() {
+        let lib = ("Lib", "{infixr ++ 5 append} {let append 0}");
+
+        let (values, errors) = desugared(&[lib, ("Main", "{import Lib} {let x (a ++ b ++ c)}")]);
+        assert_eq!((values, errors), (vec!["{append a {append b c}}".to_string()], vec![]));
+
+        // Qualified imports don't bring in operators
+        let (values, errors) = desugared(&[lib, ("Main", "{import-qualified Lib} {let x (a ++ b ++ c)}")]);
+        assert_eq!((values, errors), (vec!["{++ {++ a b} c}".to_string()], vec![]));
+    }
+
+    #[test]
+    fn sweet_expression_errors() {
+        let error = |column, message: &str| vec![(column, message.to_string())];
+
+        assert_eq!(
+            sweet_errors("{infix == 4}", "(a == b == c)"),
+            error(9, "'==' is non-associative, use parentheses to use it more than once"),
+        );
+        assert_eq!(
+            sweet_errors("{infixl + 6} {infixr ++ 6}", "(a + b ++ c)"),
+            error(8, "'+' and '++' have the same precedence but associate in different directions, use parentheses to mix them"),
+        );
+        assert_eq!(
+            sweet_errors("{infixl + 6} {infix <=> 6}", "(a + b <=> c)"),
+            error(8, "'+' and '<=>' have the same precedence and one of them is non-associative, use parentheses to mix them"),
+        );
+
+        assert_eq!(sweet_errors("", "()"), error(1, "Sweet expressions can't be empty"));
+        assert_eq!(
+            sweet_errors("", "(a +)"),
+            error(1, "Sweet expressions have to alternate between operands and operators, like (a + b)"),
+        );
+        assert_eq!(sweet_errors("", "(a 1 b)"), error(4, "Only names can be used as operators"));
+    }
+
+    #[test]
+    fn infix_errors() {
+        let cases = [
+            ("{infixl}", 1, "'infixl' requires an operator"),
+            ("{infixr 1 2}", 1, "'infixr' requires an operator"),
+            ("{infixl +}", 1, "'infixl' requires a precedence"),
+            ("{infix + 10}", 10, "Precedence has to be a number from 0 to 9"),
+            ("{infix + 1u8}", 10, "Precedence has to be a number from 0 to 9"),
+            ("{infixl + 6 \"add\"}", 13, "Operators can only stand for a function name"),
+            ("{infixl + 6 add more}", 17, "'infixl' takes an operator, a precedence and a function"),
+        ];
+
+        for (text, column, message) in cases {
+            assert_eq!(desugared(&[("Main", text)]).1, [(column, message.to_string())], "{text}");
+        }
+    }
+}
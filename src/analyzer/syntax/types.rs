@@ -0,0 +1,452 @@
+use crate::{
+    ast::*,
+    program::*,
+    token::*,
+    utils::*,
+    analyzer::lower::lower_decl,
+};
+
+// Type variable from a where clause
+struct WhereParam {
+    name: String,
+    traits: Vec<String>,
+    si: SourceInfo,
+}
+
+// {struct My-Struct {where {a Num} {b Num Eq Debug}} {field1 [a -> a]} {field2 b}}
+// Field types that are just a name are rewritten into type expressions and every type variable
+// in them gets the traits the where clause gives it.
+pub fn validate_struct(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = sexpr.si;
+    let sexpr = sexpr.sexpr_mut().unwrap();
+
+    if !validate_type_name(sexpr, "Struct", si, interner, errors) {
+        return;
+    }
+
+    let (params, fields_start) = match sexpr.get(2) {
+        Some(token) if token.match_first_identifier(Symbol::WHERE) => (validate_where(token, interner, errors), 3),
+        _ => (Vec::new(), 2),
+    };
+
+    if sexpr.len() <= fields_start {
+        errors.push(Error {
+            message: "Structs require at least one field".to_string(),
+            si,
+        });
+    }
+
+    let mut fields: Vec<(Symbol, SourceInfo)> = Vec::new();
+
+    for field in &mut sexpr[fields_start..] {
+        let field_si = field.si;
+
+        let list = match field.sexpr_mut() {
+            Some(list) if list.first().is_some_and(|t| t.is_identifier()) => list,
+            _ => {
+                errors.push(Error {
+                    message: "Fields have to be written as {name Type}".to_string(),
+                    si: field_si,
+                });
+                continue;
+            },
+        };
+
+        let name = list[0].identifier().unwrap();
+
+        if let Some((_, first_si)) = fields.iter().find(|(n, _)| *n == name) {
+            errors.push(Error {
+                message: format!("Duplicate field '{}', it was first declared at {}:{}", interner.resolve(name), first_si.line, first_si.column),
+                si: list[0].si,
+            });
+        }
+
+        fields.push((name, list[0].si));
+
+        match list.get_mut(1) {
+            Some(t) => validate_member_type(t, &params, interner, errors),
+            None => errors.push(Error {
+                message: format!("Field '{}' is missing a type", interner.resolve(name)),
+                si: field_si,
+            }),
+        }
+
+        if let Some(extra) = list.get(2) {
+            errors.push(Error {
+                message: format!("Field '{}' can only have one type", interner.resolve(name)),
+                si: extra.si,
+            });
+        }
+    }
+}
+
+// {enum My-Maybe {where a} {Some a} {None}}
+// Every variant is a constructor, the types it holds follow the same rules as struct fields.
+pub fn validate_enum(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = sexpr.si;
+    let sexpr = sexpr.sexpr_mut().unwrap();
+
+    if !validate_type_name(sexpr, "Enum", si, interner, errors) {
+        return;
+    }
+
+    let (params, variants_start) = match sexpr.get(2) {
+        Some(token) if token.match_first_identifier(Symbol::WHERE) => (validate_where(token, interner, errors), 3),
+        _ => (Vec::new(), 2),
+    };
+
+    if sexpr.len() <= variants_start {
+        errors.push(Error {
+            message: "Enums require at least one variant".to_string(),
+            si,
+        });
+    }
+
+    let mut variants: Vec<(Symbol, SourceInfo)> = Vec::new();
+
+    for variant in &mut sexpr[variants_start..] {
+        let variant_si = variant.si;
+
+        let list = match variant.sexpr_mut() {
+            Some(list) if list.first().is_some_and(|t| t.is_identifier()) => list,
+            _ => {
+                errors.push(Error {
+                    message: "Variants have to be written as {Name Type...}".to_string(),
+                    si: variant_si,
+                });
+                continue;
+            },
+        };
+
+        let name = list[0].identifier().unwrap();
+
+        if is_type_variable(interner.resolve(name)) {
+            errors.push(Error {
+                message: format!("Variant names have to start with an upper case letter, '{}' doesn't", interner.resolve(name)),
+                si: list[0].si,
+            });
+        }
+
+        if let Some((_, first_si)) = variants.iter().find(|(n, _)| *n == name) {
+            errors.push(Error {
+                message: format!("Duplicate variant '{}', it was first declared at {}:{}", interner.resolve(name), first_si.line, first_si.column),
+                si: list[0].si,
+            });
+        }
+
+        variants.push((name, list[0].si));
+
+        for t in &mut list[1..] {
+            validate_member_type(t, &params, interner, errors);
+        }
+    }
+}
+
+// Structs and enums are types so they are named like one, false if there is no name at all
+fn validate_type_name(sexpr: &[Token], kind: &str, si: SourceInfo, interner: &Interner, errors: &mut Vec<Error>) -> bool {
+    match sexpr.get(1).and_then(|t| t.identifier()) {
+        Some(name) if is_type_variable(interner.resolve(name)) => {
+            errors.push(Error {
+                message: format!("{kind} names have to start with an upper case letter, '{}' would be a type variable", interner.resolve(name)),
+                si: sexpr[1].si,
+            });
+
+            return true;
+        },
+
+        Some(_) => return true,
+
+        None => {
+            errors.push(Error {
+                message: format!("{kind}s require a name"),
+                si,
+            });
+
+            return false;
+        },
+    }
+}
+
+// {where a {b Num Eq}}, the type variables of a declaration and the traits they need
+fn validate_where(token: &Token, interner: &Interner, errors: &mut Vec<Error>) -> Vec<WhereParam> {
+    let entries = &token.sexpr().unwrap()[1..];
+    let mut params: Vec<WhereParam> = Vec::new();
+
+    if entries.is_empty() {
+        errors.push(Error {
+            message: "'where' requires at least one type variable".to_string(),
+            si: token.si,
+        });
+    }
+
+    for entry in entries {
+        let (name, traits) = match &entry.kind {
+            TokenKind::Identifier(name) => (*name, &[][..]),
+            TokenKind::SExpr(list) if list.first().is_some_and(|t| t.is_identifier()) => (list[0].identifier().unwrap(), &list[1..]),
+            _ => {
+                errors.push(Error {
+                    message: "Type variables have to be written as a or {a Trait...}".to_string(),
+                    si: entry.si,
+                });
+                continue;
+            },
+        };
+
+        let name = interner.resolve(name);
+
+        if !is_type_variable(name) {
+            errors.push(Error {
+                message: format!("Type variable '{name}' has to start with a lower case letter"),
+                si: entry.si,
+            });
+            continue;
+        }
+
+        if let Some(first) = params.iter().find(|p| p.name == name) {
+            errors.push(Error {
+                message: format!("Duplicate type variable '{name}', it was first declared at {}:{}", first.si.line, first.si.column),
+                si: entry.si,
+            });
+            continue;
+        }
+
+        let mut param = WhereParam {
+            name: name.to_string(),
+            traits: Vec::new(),
+            si: entry.si,
+        };
+
+        for t in traits {
+            match t.identifier().map(|t| interner.resolve(t)) {
+                Some(trait_name) if param.traits.iter().any(|existing| existing == trait_name) => errors.push(Error {
+                    message: format!("Trait '{trait_name}' is required twice for '{name}'"),
+                    si: t.si,
+                }),
+
+                Some(trait_name) if !is_type_variable(trait_name) => param.traits.push(trait_name.to_string()),
+
+                _ => errors.push(Error {
+                    message: "Traits have to be names starting with an upper case letter".to_string(),
+                    si: t.si,
+                }),
+            }
+        }
+
+        params.push(param);
+    }
+
+    return params;
+}
+
+// The type of a field or of a value a variant holds, a type expression or just the name of a type
+fn validate_member_type(token: &mut Token, params: &[WhereParam], interner: &Interner, errors: &mut Vec<Error>) {
+    if let TokenKind::Identifier(name) = token.kind {
+        let name = interner.resolve(name);
+
+        let t = if is_type_variable(name) {
+            Type::Generic { name: name.to_string(), traits: Vec::new() }
+        } else {
+            Type::Simple(name.to_string())
+        };
+
+        token.kind = TokenKind::TypeExpr(t);
+    }
+
+    let t = match &mut token.kind {
+        TokenKind::TypeExpr(t) => t,
+        _ => {
+            errors.push(Error {
+                message: "Field types have to be a type expression or the name of a type".to_string(),
+                si: token.si,
+            });
+            return;
+        },
+    };
+
+    // [Num a => List a] on its own says nothing the where clause couldn't, constraints are only
+    // allowed when the member holds a function
+    if !matches!(t, Type::Function { .. }) && t.has_constraints() {
+        errors.push(Error {
+            message: "Constraints can only be used when the type is a function, constrain the type variable in 'where' instead".to_string(),
+            si: token.si,
+        });
+    }
+
+    for name in t.variables() {
+        if !params.iter().any(|p| p.name == name) {
+            errors.push(Error {
+                message: format!("Type variable '{name}' has to be declared in 'where'"),
+                si: token.si,
+            });
+        }
+    }
+
+    for p in params {
+        t.add_traits(&p.name, &p.traits);
+    }
+}
+
+// Registers the struct or enum at code[index] with the functions that come with it. A struct gets
+// a constructor named after it that takes the fields in order and an accessor for every field,
+// reachable as My-Struct.field1. Every variant of an enum is a constructor, My-Maybe.Some.
+pub fn define_type(module: &mut Module, index: usize, interner: &Interner) {
+    match lower_decl(&module.code[index]) {
+        Ok(Decl::Struct { name, params, fields, .. }) => define_struct(module, index, name.name, &params, fields, interner),
+        Ok(Decl::Enum { name, params, variants, .. }) => define_enum(module, index, name.name, &params, variants, interner),
+        _ => {},
+    }
+}
+
+fn define_struct(module: &mut Module, index: usize, name: Symbol, params: &[TypeParam], fields: Vec<Field>, interner: &Interner) {
+    let struct_type = declared_type(interner.resolve(name), params, interner);
+
+    module.structs.insert(name, index);
+    module.generated.insert(name, Generated {
+        index,
+        kind: GeneratedKind::Constructor,
+        t: Type::Function {
+            params: fields.iter().map(|f| f.t.clone()).collect(),
+            variadic: None,
+            return_type: Box::new(struct_type.clone()),
+        },
+    });
+
+    for (i, field) in fields.into_iter().enumerate() {
+        module.members.insert((name, field.name.name), Generated {
+            index,
+            kind: GeneratedKind::Accessor(i),
+            t: Type::Function {
+                params: vec![struct_type.clone()],
+                variadic: None,
+                return_type: Box::new(field.t),
+            },
+        });
+    }
+}
+
+// Variants without values are a value of the enum instead of a function
+fn define_enum(module: &mut Module, index: usize, name: Symbol, params: &[TypeParam], variants: Vec<Variant>, interner: &Interner) {
+    let enum_type = declared_type(interner.resolve(name), params, interner);
+
+    module.enums.insert(name, index);
+
+    for (i, variant) in variants.into_iter().enumerate() {
+        let t = if variant.fields.is_empty() {
+            enum_type.clone()
+        } else {
+            Type::Function {
+                params: variant.fields,
+                variadic: None,
+                return_type: Box::new(enum_type.clone()),
+            }
+        };
+
+        module.members.insert((name, variant.name.name), Generated {
+            index,
+            kind: GeneratedKind::Variant(i),
+            t,
+        });
+    }
+}
+
+// My-Struct a b, the type values of a declared type have
+fn declared_type(name: &str, params: &[TypeParam], interner: &Interner) -> Type {
+    if params.is_empty() {
+        return Type::Simple(name.to_string());
+    }
+
+    let params = params.iter()
+        .map(|p| Type::Generic {
+            name: interner.resolve(p.name.name).to_string(),
+            traits: p.traits.iter().map(|t| interner.resolve(t.name).to_string()).collect(),
+        })
+        .collect();
+
+    return Type::Complex {
+        name: name.to_string(),
+        params,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tokenizer::tokenize, analyzer::syntax::validate};
+
+    // The program with text as its only module and the line, column and message of every error
+    fn validated(text: &str) -> (Program, Vec<(i64, i64, String)>) {
+        let mut program = Program::new();
+        let file = program.add_source("test.xl".to_string(), text.as_bytes().to_vec());
+        let code = tokenize(text.as_bytes(), file, program.get_interner_mut()).unwrap();
+        program.new_module("Main".to_string(), code);
+
+        let errors = validate(&mut program).into_iter()
+            .map(|e| (e.si.line, e.si.column, e.message))
+            .collect();
+
+        return (program, errors);
+    }
+
+    fn errors(text: &str) -> Vec<(i64, i64, String)> {
+        validated(text).1
+    }
+
+    fn error(line: i64, column: i64, message: &str) -> (i64, i64, String) {
+        (line, column, message.to_string())
+    }
+
+    #[test]
+    fn enum_variants() {
+        let (program, errors) = validated("
+{enum My-Maybe {where a} {Some a} {None}}
+{let x My-Maybe.None}
+{let y {My-Maybe.Some 1}}
+");
+        assert_eq!(errors, []);
+
+        let interner = program.get_interner();
+        let symbol = |name| interner.get(name).unwrap();
+        let module = &program.get_modules()[0];
+
+        let a = Type::Generic { name: "a".to_string(), traits: Vec::new() };
+        let maybe = Type::Complex { name: "My-Maybe".to_string(), params: vec![a.clone()] };
+
+        assert!(module.enums.contains_key(&symbol("My-Maybe")));
+
+        let some = &module.members[&(symbol("My-Maybe"), symbol("Some"))];
+        assert_eq!(some.kind, GeneratedKind::Variant(0));
+        assert_eq!(some.t, Type::Function {
+            params: vec![a],
+            variadic: None,
+            return_type: Box::new(maybe.clone()),
+        });
+
+        // Variants without values are values of the enum
+        let none = &module.members[&(symbol("My-Maybe"), symbol("None"))];
+        assert_eq!(none.kind, GeneratedKind::Variant(1));
+        assert_eq!(none.t, maybe);
+    }
+
+    #[test]
+    fn enum_errors() {
+        let cases = [
+            ("{enum}", 1, "Enums require a name"),
+            ("{enum maybe {A}}", 7,
+                "Enum names have to start with an upper case letter, 'maybe' would be a type variable"),
+            ("{enum E}", 1, "Enums require at least one variant"),
+            ("{enum E A}", 9, "Variants have to be written as {Name Type...}"),
+            ("{enum E {a}}", 10, "Variant names have to start with an upper case letter, 'a' doesn't"),
+            ("{enum E {A} {A}}", 14, "Duplicate variant 'A', it was first declared at 1:10"),
+            ("{enum E {A b}}", 12, "Type variable 'b' has to be declared in 'where'"),
+            ("{enum E {A \"x\"}}", 12, "Field types have to be a type expression or the name of a type"),
+            ("{enum E {where} {A}}", 9, "'where' requires at least one type variable"),
+            ("{enum E {where A} {B}}", 16, "Type variable 'A' has to start with a lower case letter"),
+            ("{enum E {where a a} {A a}}", 18, "Duplicate type variable 'a', it was first declared at 1:16"),
+            ("{enum E {where {a Num Num}} {A a}}", 23, "Trait 'Num' is required twice for 'a'"),
+        ];
+
+        for (text, column, message) in cases {
+            assert_eq!(errors(text), [error(1, column, message)], "{text}");
+        }
+    }
+}
@@ -1,84 +1,339 @@
+use std::collections::HashMap;
+
 use crate::{
     program::*,
     token::*,
     utils::*,
+    analyzer::utils::{code_children, is_definition, is_import, is_infix, is_macro, is_quotation},
+    analyzer::macros::expand_macros,
+    analyzer::lower::lower_module,
+    analyzer::syntax::*,
+    analyzer::resolve::{check_imports, check_names},
+    analyzer::init_order::{initialization_order, Dependencies},
 };
 
 // Checks top level constructs for syntax errors + collection of functions
 pub fn validate(program: &mut Program) -> Vec<Error> {
-    let mut errors = Vec::new();
+    let mut errors = expand_macros(program);
+    let (modules, interner) = program.get_modules_mut_with_interner();
+
+    for module in modules {
+        validate_module(module, interner, &mut errors);
+    }
+
+    // Every module has to be collected before imports between them can be checked
+    errors.extend(check_imports(program));
 
-    for module in program.get_modules_mut() {
-        validate_module(module, &mut errors);
+    // Operators and variadic functions can be imported and the calls sweet expressions turn into
+    // can be variadic
+    for id in program.get_ids() {
+        let operators = visible_operators(program, id);
+        let functions = visible_variadic_functions(program, id);
+        let (modules, interner) = program.get_modules_mut_with_interner();
+
+        desugar_sweet_exprs(&mut modules[id].code, &operators, interner, &mut errors);
+        bind_variadic_calls(&mut modules[id].code, &functions, interner, &mut errors);
     }
 
+    // Code isn't rewritten after this so every definition is lowered once for the passes left
+    let mut dependencies = Dependencies::new();
+
+    for m in program.get_modules() {
+        for decl in lower_module(m) {
+            match decl {
+                Ok(decl) => {
+                    check_names(program, m.id, &decl, &mut errors);
+                    dependencies.add(program, m.id, &decl, &mut errors);
+                },
+
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+
+    // Top level values can only be ordered once the calls in them are what they will be run as
+    let (order, order_errors) = initialization_order(program, dependencies);
+    program.set_init_order(order);
+    errors.extend(order_errors);
+
     return errors;
 }
 
 // For repl use
-pub fn validate_code(code: &mut Vec<Token>) -> Vec<Error> {
+pub fn validate_code(code: &mut Vec<Token>, interner: &Interner) -> Vec<Error> {
     let mut errors = Vec::new();
 
     for token in code {
-        validate_token(token, &mut errors);
+        keep_definition_doc(token);
+
+        if token.is_sexpr() {
+            validate_sexpr(token, interner, &mut errors);
+        } else {
+            validate_token(token, interner, &mut errors);
+        }
     }
 
     return errors;
 }
 
-fn validate_module(module: &mut Module, errors: &mut Vec<Error>) {
+fn validate_module(module: &mut Module, interner: &Interner, errors: &mut Vec<Error>) {
+    let mut types = Vec::new();
+    let mut imports = Vec::new();
+    let mut operators = Vec::new();
+
+    // Where every top level name was first defined and if it was redefined, they all share one
+    // namespace
+    let mut defined: HashMap<Symbol, (SourceInfo, bool)> = HashMap::new();
+
     for (index, token) in module.code.iter_mut().enumerate() {
+        keep_definition_doc(token);
+
         if token.is_sexpr() {
             let n_errors = errors.len();
-            validate_sexpr(token, errors);
-            
+            validate_sexpr(token, interner, errors);
+
+            // The first definition stays, later ones are only reported. A definition with errors
+            // still takes its name so redefining it is reported too.
+            if is_definition(token) && !define_name(token, &mut defined, interner, errors) {
+                continue;
+            }
+
             if errors.len() == n_errors {
-                if token.match_first_identifier("let") {
+                if token.match_first_identifier(Symbol::LET) {
                     let name = token.sexpr().unwrap().get(1).unwrap().identifier().unwrap();
-                    module.variables.insert(name.clone(), index);
-                } else if token.match_first_identifier("procedure") {
+                    module.variables.insert(name, index);
+                } else if token.match_first_identifier(Symbol::PROCEDURE) {
                     let name = token.sexpr().unwrap().get(1).unwrap().identifier().unwrap();
-                    module.procedures.insert(name.clone(), index);
+                    module.procedures.insert(name, index);
+                } else if token.match_first_identifier(Symbol::STRUCT) || token.match_first_identifier(Symbol::ENUM) {
+                    types.push(index);
+                } else if is_import(token) {
+                    imports.push(index);
+                } else if is_infix(token) {
+                    operators.push(index);
+                } else if !is_definition(token) {
+                    module.expressions.push(index);
                 }
             }
         } else {
             module.expressions.push(index);
         }
     }
+
+    for index in types {
+        define_type(module, index, interner);
+    }
+
+    for index in imports {
+        define_import(module, index, interner);
+    }
+
+    for index in operators {
+        define_operator(module, index);
+    }
 }
 
-fn validate_token(token: &mut Token, errors: &mut Vec<Error>) {
-    if token.is_sexpr() {
-        validate_sexpr(token, errors);
+// False if the name of the definition is already taken, every redefinition gets an error and
+// the first definition gets one the first time it is redefined
+fn define_name(token: &Token, defined: &mut HashMap<Symbol, (SourceInfo, bool)>, interner: &Interner, errors: &mut Vec<Error>) -> bool {
+    // Definitions without a name are reported by validate_sexpr
+    let name = match token.sexpr().unwrap().get(1) {
+        Some(name) if name.is_identifier() => name,
+        _ => return true,
+    };
+
+    let symbol = name.identifier().unwrap();
+
+    let (first_si, redefined) = match defined.get_mut(&symbol) {
+        Some((first_si, redefined)) => (*first_si, std::mem::replace(redefined, true)),
+        None => {
+            defined.insert(symbol, (name.si, false));
+            return true;
+        },
+    };
+
+    let name_str = interner.resolve(symbol);
+
+    errors.push(Error {
+        message: format!("Duplicate definition of '{name_str}', it was first defined at {}:{}", first_si.line, first_si.column),
+        si: name.si,
+    });
+
+    if !redefined {
+        errors.push(Error {
+            message: format!("'{name_str}' is first defined here and defined again at {}:{}", name.si.line, name.si.column),
+            si: first_si,
+        });
     }
+
+    return false;
+}
+
+// Doc comments only document definitions, anywhere else they are just comments
+fn keep_definition_doc(token: &mut Token) {
+    if !is_definition(token) {
+        token.doc = None;
+    }
+}
+
+// Definitions, imports and infix declarations only make sense at the top level, anything else
+// would have to be lowered into an expression
+fn is_declaration(token: &Token) -> bool {
+    is_definition(token) || is_import(token) || is_infix(token)
 }
 
-fn validate_sexpr(sexpr: &mut Token, errors: &mut Vec<Error>) {
-    if sexpr.match_first_identifier("function") {
-        validate_function(sexpr, errors);
-    } else if sexpr.match_first_identifier("procedure") {
-        validate_procedure(sexpr, errors);
-    } else if sexpr.match_first_identifier("let") {
-        validate_let(sexpr, errors);
-    } else if sexpr.match_first_identifier("fun") {
-        validate_fun(sexpr, errors);
+fn validate_token(token: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    if is_declaration(token) {
+        let keyword = &token.sexpr().unwrap()[0];
+
+        errors.push(Error {
+            message: format!("'{}' can only be used at the top level", interner.resolve(keyword.identifier().unwrap())),
+            si: keyword.si,
+        });
+    } else if token.is_sexpr() {
+        validate_sexpr(token, interner, errors);
+    } else if token.is_label() {
+        errors.push(Error {
+            message: "Labelled arguments can only be used in function calls".to_string(),
+            si: token.si,
+        });
+    } else if token.is_type() {
+        errors.push(Error {
+            message: "Type expressions can only be used as the type of a definition".to_string(),
+            si: token.si,
+        });
+    } else if token.is_variadic() {
+        errors.push(Error {
+            message: "Variadic names can only be used in parameter lists".to_string(),
+            si: token.si,
+        });
+    } else if let TokenKind::SweetExpr(sweet) = &mut token.kind {
+        for token in sweet {
+            validate_token(token, interner, errors);
+        }
+    }
+}
+
+fn validate_sexpr(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    if sexpr.match_first_identifier(Symbol::FUNCTION) {
+        validate_function(sexpr, interner, errors);
+    } else if sexpr.match_first_identifier(Symbol::PROCEDURE) {
+        validate_procedure(sexpr, interner, errors);
+    } else if sexpr.match_first_identifier(Symbol::LET) {
+        validate_let(sexpr, interner, errors);
+    } else if sexpr.match_first_identifier(Symbol::FUN) {
+        validate_fun(sexpr, interner, errors);
+    } else if sexpr.match_first_identifier(Symbol::STRUCT) {
+        validate_struct(sexpr, interner, errors);
+    } else if sexpr.match_first_identifier(Symbol::ENUM) {
+        validate_enum(sexpr, interner, errors);
+    } else if is_import(sexpr) {
+        validate_import(sexpr, interner, errors);
+    } else if is_infix(sexpr) {
+        validate_infix(sexpr, interner, errors);
+    } else if is_macro(sexpr) {
+        validate_macro(sexpr, errors);
+    } else if is_quotation(sexpr) {
+        validate_quotation(sexpr, interner, errors);
     } else {
-        validate_call(sexpr, errors);
+        validate_call(sexpr, interner, errors);
     }
 }
 
-fn validate_call(sexpr: &mut Token, errors: &mut Vec<Error>) {
+fn validate_call(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
     let sexpr = sexpr.sexpr_mut().unwrap();
+
+    if let Some(function) = sexpr.first_mut() {
+        validate_token(function, interner, errors);
+    }
+
     if sexpr.len() > 1 {
-        for token in &mut sexpr[1..] {
-            validate_token(token, errors);
+        validate_arguments(&mut sexpr[1..], interner, errors);
+    }
+}
+
+// {quote x}, {quasiquote x} and the unquotes that can only be used in a quasiquote
+fn validate_quotation(token: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = token.si;
+    let sexpr = token.sexpr().unwrap();
+    let symbol = sexpr[0].identifier().unwrap();
+    let name = interner.resolve(symbol);
+
+    if sexpr.len() != 2 {
+        errors.push(Error {
+            message: format!("'{name}' takes exactly one expression"),
+            si,
+        });
+        return;
+    }
+
+    if symbol == Symbol::UNQUOTE || symbol == Symbol::UNQUOTE_SPLICING {
+        errors.push(Error {
+            message: format!("'{name}' can only be used inside of a quasiquote"),
+            si,
+        });
+    } else if symbol == Symbol::QUASIQUOTE {
+        for token in code_children(token) {
+            validate_token(token, interner, errors);
+        }
+    }
+}
+
+// {make-person "Lyra" @age 24 @gender Gender.Female}
+// Every label needs a value and positional arguments have to come before labelled ones
+fn validate_arguments(args: &mut [Token], interner: &Interner, errors: &mut Vec<Error>) {
+    let mut labels: Vec<(Symbol, SourceInfo)> = Vec::new();
+    let mut i = 0;
+
+    while i < args.len() {
+        let (label, rest) = args[i..].split_first_mut().unwrap();
+
+        let name = match &label.kind {
+            TokenKind::Label(name) => *name,
+            _ => {
+                if let Some((_, label_si)) = labels.last() {
+                    errors.push(Error {
+                        message: format!("Positional arguments can not come after labelled arguments, the last label was at {}:{}", label_si.line, label_si.column),
+                        si: label.si,
+                    });
+                }
+
+                validate_token(label, interner, errors);
+                i += 1;
+                continue;
+            },
+        };
+
+        match rest.first_mut() {
+            Some(value) if !value.is_label() => {
+                validate_token(value, interner, errors);
+                i += 2;
+            },
+
+            _ => {
+                errors.push(Error {
+                    message: format!("Label '@{}' is missing a value", interner.resolve(name)),
+                    si: label.si,
+                });
+
+                i += 1;
+            },
+        }
+
+        if let Some((_, first_si)) = labels.iter().find(|(n, _)| *n == name) {
+            errors.push(Error {
+                message: format!("Duplicate label '@{}', it was first used at {}:{}", interner.resolve(name), first_si.line, first_si.column),
+                si: label.si,
+            });
         }
+
+        labels.push((name, label.si));
     }
 }
 
-fn validate_fun(sexpr: &mut Token, errors: &mut Vec<Error>) {
+fn validate_fun(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = sexpr.si;
     let sexpr = sexpr.sexpr_mut().unwrap();
-    let si = sexpr[0].si;
 
     let mut has_params = false;
     let mut has_valid_params = true;
@@ -87,11 +342,13 @@ fn validate_fun(sexpr: &mut Token, errors: &mut Vec<Error>) {
         if let TokenKind::SExpr(params) = &token.kind {
             has_params = true;
             for p in params {
-                if !p.is_identifier() {
+                if !p.is_identifier() && !p.is_variadic() {
                     has_valid_params = false;
                     break;
                 }
             }
+
+            validate_variadic_params(params, errors);
         }
     }
 
@@ -108,8 +365,8 @@ fn validate_fun(sexpr: &mut Token, errors: &mut Vec<Error>) {
     }
 
     if sexpr.len() >= 3 {
-        for token in &mut sexpr[3..] {
-            validate_token(token, errors);
+        for token in &mut sexpr[2..] {
+            validate_token(token, interner, errors);
         }
     } else {
         errors.push(Error {
@@ -119,12 +376,107 @@ fn validate_fun(sexpr: &mut Token, errors: &mut Vec<Error>) {
     }
 }
 
-fn validate_let(sexpr: &mut Token, errors: &mut Vec<Error>) {
+// {macro name {params} body...}, the body runs at compile time and is checked when the macros are
+// expanded, see analyzer::macros
+fn validate_macro(sexpr: &mut Token, errors: &mut Vec<Error>) {
+    let si = sexpr.si;
+    let sexpr = sexpr.sexpr().unwrap();
+
+    if !sexpr.get(1).is_some_and(|t| t.is_identifier()) {
+        errors.push(Error {
+            message: "Macros require a name".to_string(),
+            si,
+        });
+    }
+
+    match sexpr.get(2).and_then(|t| t.sexpr()) {
+        Some(params) if params.iter().all(|p| p.is_identifier() || p.is_variadic()) => validate_variadic_params(params, errors),
+        Some(_) => errors.push(Error {
+            message: "Macro parameters have to be identifiers".to_string(),
+            si,
+        }),
+
+        None => errors.push(Error {
+            message: "Macros require a parameter list".to_string(),
+            si,
+        }),
+    }
+
+    if sexpr.len() < 4 {
+        errors.push(Error {
+            message: "Macros require at least one expression in them".to_string(),
+            si,
+        });
+    }
+}
+
+// {a b rest..}, a function can only have one variadic parameter and it has to be the last one
+fn validate_variadic_params(params: &[Token], errors: &mut Vec<Error>) {
+    let mut variadics = params.iter().filter(|p| p.is_variadic());
+
+    if let Some(second) = variadics.nth(1) {
+        errors.push(Error {
+            message: "Functions can have at most one variadic parameter".to_string(),
+            si: second.si,
+        });
+    } else if let Some((i, param)) = params.iter().enumerate().find(|(_, p)| p.is_variadic()) {
+        if i != params.len() - 1 {
+            errors.push(Error {
+                message: "Only the last parameter can be variadic".to_string(),
+                si: param.si,
+            });
+        }
+    }
+}
+
+// The parameters of a lambda have to line up with the function type it is given
+fn validate_function_type(t: &Token, fun: &Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let (type_params, type_variadic) = match &t.kind {
+        TokenKind::TypeExpr(Type::Function { params, variadic, .. }) => (params, variadic),
+        _ => return,
+    };
+
+    if !fun.match_first_identifier(Symbol::FUN) {
+        return;
+    }
+
+    let params = match fun.sexpr().unwrap().get(1).and_then(|p| p.sexpr()) {
+        Some(params) => params,
+        None => return,
+    };
+
+    let params_si = fun.sexpr().unwrap()[1].si;
+    let variadic = params.last().filter(|p| p.is_variadic());
+    let n_fixed = params.iter().filter(|p| !p.is_variadic()).count();
+
+    if n_fixed != type_params.len() {
+        errors.push(Error {
+            message: format!("The function type has {} parameters but {} were declared", type_params.len(), n_fixed),
+            si: params_si,
+        });
+    }
+
+    match (variadic, type_variadic) {
+        (Some(param), None) => errors.push(Error {
+            message: format!("Parameter '{}..' is variadic but the function type has no variadic parameter", interner.resolve(param.identifier_or_variadic().unwrap())),
+            si: param.si,
+        }),
+
+        (None, Some(_)) => errors.push(Error {
+            message: "The function type is variadic but there is no variadic parameter".to_string(),
+            si: params_si,
+        }),
+
+        _ => {},
+    }
+}
+
+fn validate_let(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = sexpr.si;
     let sexpr = sexpr.sexpr_mut().unwrap();
-    let si = sexpr[0].si;
 
-    let has_name = sexpr.get(1).map_or(false, |t| t.is_identifier());
-    let has_type = sexpr.get(2).map_or(false, |t| t.is_type());
+    let has_name = sexpr.get(1).is_some_and(|t| t.is_identifier());
+    let has_type = sexpr.get(2).is_some_and(|t| t.is_type());
 
     if !has_name {
         errors.push(Error {
@@ -134,14 +486,13 @@ fn validate_let(sexpr: &mut Token, errors: &mut Vec<Error>) {
     }
 
     if !has_type {
-        sexpr.insert(2, Token {
-            kind: TokenKind::TypeExpr(Type::Unknown),
-            si: SourceInfo::default(),
-        });
+        let type_si = sexpr.get(1).map_or(si, |t| t.si).after();
+        sexpr.insert(2, Token::new(TokenKind::TypeExpr(Type::Unknown), type_si));
     }
 
     if let Some(value) = sexpr.get_mut(3) {
-        validate_token(value, errors);
+        validate_token(value, interner, errors);
+        validate_function_type(&sexpr[2], &sexpr[3], interner, errors);
     } else {
         errors.push(Error {
             message: "Variables require an initial value".to_string(),
@@ -150,12 +501,12 @@ fn validate_let(sexpr: &mut Token, errors: &mut Vec<Error>) {
     }
 }
 
-fn validate_procedure(sexpr: &mut Token, errors: &mut Vec<Error>) {
+fn validate_procedure(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = sexpr.si;
     let sexpr = sexpr.sexpr_mut().unwrap();
-    let si = sexpr[0].si;
 
-    let has_name = sexpr.get(1).map_or(false, |t| t.is_identifier());
-    let has_type = sexpr.get(2).map_or(false, |t| t.is_type());
+    let has_name = sexpr.get(1).is_some_and(|t| t.is_identifier());
+    let has_type = sexpr.get(2).is_some_and(|t| t.is_type());
 
     if !has_name {
         errors.push(Error {
@@ -165,25 +516,23 @@ fn validate_procedure(sexpr: &mut Token, errors: &mut Vec<Error>) {
     }
 
     if !has_type {
-        sexpr.insert(2, Token {
-            kind: TokenKind::TypeExpr(Type::Unknown),
-            si: SourceInfo::default(),
-        });
+        let type_si = sexpr.get(1).map_or(si, |t| t.si).after();
+        sexpr.insert(2, Token::new(TokenKind::TypeExpr(Type::Unknown), type_si));
     }
 
     if sexpr.len() >= 3 {
         for token in &mut sexpr[3..] {
-            validate_token(token, errors);
+            validate_token(token, interner, errors);
         }
     }
 }
 
-fn validate_function(sexpr: &mut Token, errors: &mut Vec<Error>) {
+fn validate_function(sexpr: &mut Token, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = sexpr.si;
     let sexpr = sexpr.sexpr_mut().unwrap();
-    let si = sexpr[0].si;
 
-    let has_name = sexpr.get(1).map_or(false, |t| t.is_identifier());
-    let has_type = sexpr.get(2).map_or(false, |t| t.is_type());
+    let has_name = sexpr.get(1).is_some_and(|t| t.is_identifier());
+    let has_type = sexpr.get(2).is_some_and(|t| t.is_type());
 
     let mut has_params = false;
     let mut has_valid_params = true;
@@ -194,7 +543,7 @@ fn validate_function(sexpr: &mut Token, errors: &mut Vec<Error>) {
         if let TokenKind::SExpr(params) = &token.kind {
             has_params = true;
             for param in params {
-                if !param.is_identifier() {
+                if !param.is_identifier() && !param.is_variadic() {
                     has_valid_params = false;
                     break;
                 }
@@ -223,19 +572,17 @@ fn validate_function(sexpr: &mut Token, errors: &mut Vec<Error>) {
 
     if has_params && has_valid_params {
         if !has_type {
-            sexpr.insert(2, Token {
-                kind: TokenKind::TypeExpr(Type::Unknown),
-                si: SourceInfo::default(),
-            });
+            let type_si = sexpr[1].si.after();
+            sexpr.insert(2, Token::new(TokenKind::TypeExpr(Type::Unknown), type_si));
         }
 
-        sexpr[0] = Token {
-            kind: TokenKind::Identifier("let".to_string()),
-            si,
-        };
+        sexpr[0] = Token::new(TokenKind::Identifier(Symbol::LET), sexpr[0].si);
+
+        // The lambda covers the parameters and the body in the source
+        let body_si = sexpr[3].si.to(sexpr.last().unwrap().si);
 
         let mut body = vec![
-            Token { kind: TokenKind::Identifier("fun".to_string()), si: SourceInfo::default() },
+            Token::new(TokenKind::Identifier(Symbol::FUN), sexpr[0].si),
             sexpr.remove(3),
         ];
 
@@ -244,8 +591,89 @@ fn validate_function(sexpr: &mut Token, errors: &mut Vec<Error>) {
         }
 
         body[2..].reverse();
-        sexpr.push(Token { kind: TokenKind::SExpr(body), si: SourceInfo::default() });
+        sexpr.push(Token::new(TokenKind::SExpr(body), body_si));
+
+        validate_sexpr(&mut sexpr[3], interner, errors);
+        validate_function_type(&sexpr[2], &sexpr[3], interner, errors);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize;
+
+    // Line, column and message of every error in a program with one module
+    fn errors(text: &str) -> Vec<(i64, i64, String)> {
+        let mut program = Program::new();
+        let file = program.add_source("test.xl".to_string(), text.as_bytes().to_vec());
+        let code = tokenize(text.as_bytes(), file, program.get_interner_mut()).unwrap();
+        program.new_module("Main".to_string(), code);
+
+        return validate(&mut program).into_iter().map(|e| (e.si.line, e.si.column, e.message)).collect();
+    }
+
+    fn error(line: i64, column: i64, message: &str) -> (i64, i64, String) {
+        (line, column, message.to_string())
+    }
+
+    #[test]
+    fn labelled_arguments() {
+        assert_eq!(errors("{make-person \"Lyra\" @age 24 @gender female}"), []);
+
+        assert_eq!(errors("{f @a 1 2}"), [
+            error(1, 9, "Positional arguments can not come after labelled arguments, the last label was at 1:4"),
+        ]);
+
+        assert_eq!(errors("{f @a @b 1}"), [error(1, 4, "Label '@a' is missing a value")]);
+        assert_eq!(errors("{f @a}"), [error(1, 4, "Label '@a' is missing a value")]);
+        assert_eq!(errors("{f @a 1 @a 2}"), [error(1, 9, "Duplicate label '@a', it was first used at 1:4")]);
+        assert_eq!(errors("{let x @a}"), [
+            error(1, 8, "Labelled arguments can only be used in function calls"),
+        ]);
+    }
+
+    #[test]
+    fn duplicate_definitions() {
+        let text = "{let x 1}
+{let x 2}
+{procedure x [Unit] {println 1}}";
+
+        assert_eq!(errors(text), [
+            error(2, 6, "Duplicate definition of 'x', it was first defined at 1:6"),
+            error(1, 6, "'x' is first defined here and defined again at 2:6"),
+            error(3, 12, "Duplicate definition of 'x', it was first defined at 1:6"),
+        ]);
+
+        // Types and values share one namespace
+        assert_eq!(errors("{enum Color {Red}}\n{struct Color {red Int}}"), [
+            error(2, 9, "Duplicate definition of 'Color', it was first defined at 1:7"),
+            error(1, 7, "'Color' is first defined here and defined again at 2:9"),
+        ]);
+    }
+
+    // A definition with errors in it still takes its name
+    #[test]
+    fn duplicate_of_invalid_definition() {
+        assert_eq!(errors("{let x}\n{let x 1}"), [
+            error(1, 1, "Variables require an initial value"),
+            error(2, 6, "Duplicate definition of 'x', it was first defined at 1:6"),
+            error(1, 6, "'x' is first defined here and defined again at 2:6"),
+        ]);
+    }
+
+    // Only the first definition is kept
+    #[test]
+    fn first_definition_is_kept() {
+        let text = "{let x 1}\n{let x 2}";
+
+        let mut program = Program::new();
+        let file = program.add_source("test.xl".to_string(), text.as_bytes().to_vec());
+        let code = tokenize(text.as_bytes(), file, program.get_interner_mut()).unwrap();
+        program.new_module("Main".to_string(), code);
+        validate(&mut program);
+
+        let x = program.get_interner().get("x").unwrap();
+        assert_eq!(program.get_modules()[0].variables[&x], 0);
     }
-    
-    validate_sexpr(&mut sexpr[3], errors);
 }
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use crate::{
+    program::*,
+    token::*,
+    utils::*,
+    analyzer::utils::{code_children, is_quotation, is_variable, lambda_params},
+};
+
+// Calls to variadic functions get their extra arguments packed into a list so the function
+// always receives exactly one argument per parameter.
+// {+ 1 2 3} with {function + [a, a.. -> a] {x rest..} ...} becomes {+ 1 {list 2 3}}
+pub fn bind_variadic_calls(code: &mut [Token], functions: &HashMap<Symbol, usize>, interner: &Interner, errors: &mut Vec<Error>) {
+    if functions.is_empty() {
+        return;
+    }
+
+    let mut shadowed = Vec::new();
+    for token in code {
+        bind_token(token, functions, &mut shadowed, interner, errors);
+    }
+}
+
+// Variadic functions a module can call without a module in front of them, its own and the ones
+// its unqualified imports bring in. Its own definitions hide imported ones even if they aren't
+// variadic.
+pub fn visible_variadic_functions(program: &Program, module: usize) -> HashMap<Symbol, usize> {
+    let m = program.get_module_by_id(module).unwrap();
+    let mut functions = HashMap::new();
+
+    for import in m.imports.iter().filter(|import| !import.qualified) {
+        let target = match program.get_module_by_name(&import.module) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        for (name, n_fixed) in collect_variadic_functions(target) {
+            if import.allows(name) && !m.has_definition(name) {
+                functions.insert(name, n_fixed);
+            }
+        }
+    }
+
+    functions.extend(collect_variadic_functions(m));
+    return functions;
+}
+
+// Name of every top level variadic function of a module and how many fixed parameters it has
+fn collect_variadic_functions(module: &Module) -> HashMap<Symbol, usize> {
+    return module.variables.values()
+        .filter_map(|index| variadic_function(&module.code[*index]))
+        .collect();
+}
+
+// {let name type {fun {x rest..} ...}} is a variadic function with one fixed parameter
+pub fn variadic_function(token: &Token) -> Option<(Symbol, usize)> {
+    if !is_variable(token) {
+        return None;
+    }
+
+    let sexpr = token.sexpr().unwrap();
+    let value = sexpr.get(3)?;
+
+    if !value.match_first_identifier(Symbol::FUN) {
+        return None;
+    }
+
+    let params = value.sexpr().unwrap().get(1)?.sexpr()?;
+
+    if !params.last()?.is_variadic() {
+        return None;
+    }
+
+    return Some((sexpr[1].identifier()?, params.len() - 1));
+}
+
+fn bind_token(token: &mut Token, functions: &HashMap<Symbol, usize>, shadowed: &mut Vec<Symbol>, interner: &Interner, errors: &mut Vec<Error>) {
+    let si = token.si;
+    let is_call = token.is_sexpr() && !is_quotation(token);
+
+    let n_shadowed = shadowed.len();
+    shadowed.extend(lambda_params(token));
+
+    for token in code_children(&mut *token) {
+        bind_token(token, functions, shadowed, interner, errors);
+    }
+
+    if is_call {
+        bind_call(token.sexpr_mut().unwrap(), si, functions, shadowed, interner, errors);
+    }
+
+    shadowed.truncate(n_shadowed);
+}
+
+fn bind_call(sexpr: &mut Vec<Token>, si: SourceInfo, functions: &HashMap<Symbol, usize>, shadowed: &[Symbol], interner: &Interner, errors: &mut Vec<Error>) {
+    let name = match sexpr.first().and_then(|t| t.identifier()) {
+        Some(name) if !shadowed.contains(&name) => name,
+        _ => return,
+    };
+
+    let n_fixed = match functions.get(&name) {
+        Some(n_fixed) => *n_fixed,
+        None => return,
+    };
+
+    // Labelled arguments are matched by name, not by position
+    if sexpr[1..].iter().any(|t| t.is_label()) {
+        return;
+    }
+
+    let n_args = sexpr.len() - 1;
+    if n_args < n_fixed {
+        errors.push(Error {
+            message: format!("'{}' takes at least {n_fixed} arguments but {n_args} were given", interner.resolve(name)),
+            si,
+        });
+        return;
+    }
+
+    let rest = sexpr.split_off(n_fixed + 1);
+    let rest_si = match (rest.first(), rest.last()) {
+        (Some(first), Some(last)) => first.si.to(last.si),
+        _ => sexpr.last().unwrap().si.after(),
+    };
+
+    let mut list = vec![Token::new(TokenKind::Identifier(Symbol::LIST), rest_si)];
+    list.extend(rest);
+
+    sexpr.push(Token::new(TokenKind::SExpr(list), rest_si));
+}
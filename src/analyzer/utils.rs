@@ -1,13 +1,150 @@
-use crate::token::*;
+use crate::{
+    token::*,
+    utils::Symbol,
+};
 
 pub fn is_variable(token: &Token) -> bool {
-    token.match_first_identifier("let")
+    token.match_first_identifier(Symbol::LET)
 }
 
 pub fn is_procedure(token: &Token) -> bool {
-    token.match_first_identifier("procedure")
+    token.match_first_identifier(Symbol::PROCEDURE)
+}
+
+pub fn is_struct(token: &Token) -> bool {
+    token.match_first_identifier(Symbol::STRUCT)
+}
+
+pub fn is_enum(token: &Token) -> bool {
+    token.match_first_identifier(Symbol::ENUM)
+}
+
+pub fn is_import(token: &Token) -> bool {
+    token.match_first_identifier(Symbol::IMPORT) || token.match_first_identifier(Symbol::IMPORT_QUALIFIED)
+}
+
+pub fn is_infix(token: &Token) -> bool {
+    [Symbol::INFIXL, Symbol::INFIXR, Symbol::INFIX].iter().any(|s| token.match_first_identifier(*s))
+}
+
+pub fn is_macro(token: &Token) -> bool {
+    token.match_first_identifier(Symbol::MACRO)
 }
 
 pub fn is_lambda(token: &Token) -> bool {
-    token.match_first_identifier("fun")
+    token.match_first_identifier(Symbol::FUN)
+}
+
+// Forms that doc comments can be attached to
+pub fn is_definition(token: &Token) -> bool {
+    [Symbol::FUNCTION, Symbol::PROCEDURE, Symbol::LET, Symbol::STRUCT, Symbol::ENUM, Symbol::MACRO].iter().any(|s| token.match_first_identifier(*s))
+}
+
+// {quote x} and the other forms the reader expands 'x, `x, ,x and ,@x into
+pub fn is_quotation(token: &Token) -> bool {
+    [Symbol::QUOTE, Symbol::QUASIQUOTE, Symbol::UNQUOTE, Symbol::UNQUOTE_SPLICING].iter().any(|s| token.match_first_identifier(*s))
+}
+
+// Passes that walk code read or rewrite it the same way, so they are written once for both kinds
+// of reference to a token
+pub trait TokenRef: Sized {
+    fn token(&self) -> &Token;
+
+    // The tokens of an s-expression or sweet expression, nothing for any other token
+    fn into_list(self) -> Vec<Self>;
+}
+
+impl TokenRef for &Token {
+    fn token(&self) -> &Token {
+        self
+    }
+
+    fn into_list(self) -> Vec<Self> {
+        match &self.kind {
+            TokenKind::SExpr(list) | TokenKind::SweetExpr(list) => list.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl TokenRef for &mut Token {
+    fn token(&self) -> &Token {
+        self
+    }
+
+    fn into_list(self) -> Vec<Self> {
+        match &mut self.kind {
+            TokenKind::SExpr(list) | TokenKind::SweetExpr(list) => list.iter_mut().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+// The code right inside of token. Quoted code is data so there is none in a quote and a
+// quasiquote only has the parts of it that are unquoted, every other list is all code.
+pub fn code_children<T: TokenRef>(token: T) -> Vec<T> {
+    if token.token().match_first_identifier(Symbol::QUASIQUOTE) {
+        let mut code = Vec::new();
+
+        for template in token.into_list().into_iter().skip(1) {
+            collect_unquoted(template, 1, &mut code);
+        }
+
+        return code;
+    }
+
+    if is_quotation(token.token()) {
+        return Vec::new();
+    }
+
+    return token.into_list();
+}
+
+// Expressions inside of the x in {quasiquote x} that are evaluated instead of quoted
+pub fn unquoted<T: TokenRef>(template: T) -> Vec<T> {
+    let mut unquoted = Vec::new();
+    collect_unquoted(template, 1, &mut unquoted);
+
+    return unquoted;
+}
+
+// Depth counts the quasiquotes token is in, an unquote only leaves the last one so depth never
+// gets below 1
+fn collect_unquoted<T: TokenRef>(token: T, depth: usize, acc: &mut Vec<T>) {
+    let is_unquote = token.token().match_first_identifier(Symbol::UNQUOTE) || token.token().match_first_identifier(Symbol::UNQUOTE_SPLICING);
+
+    // {unquote x} with nothing left to unquote, x is evaluated
+    if is_unquote && depth == 1 {
+        acc.extend(token.into_list().into_iter().skip(1));
+        return;
+    }
+
+    let depth = if token.token().match_first_identifier(Symbol::QUASIQUOTE) {
+        depth + 1
+    } else if is_unquote {
+        depth - 1
+    } else {
+        depth
+    };
+
+    for token in token.into_list() {
+        collect_unquoted(token, depth, acc);
+    }
+}
+
+// Parameters of a lambda hide top level definitions and macros with the same name inside of it,
+// nothing for anything that isn't a lambda. {function name [type] {params} body...} is one too
+// until validation turns it into a let.
+pub fn lambda_params(token: &Token) -> Vec<Symbol> {
+    let index = if is_lambda(token) {
+        1
+    } else if token.match_first_identifier(Symbol::FUNCTION) {
+        if token.sexpr().unwrap().get(2).is_some_and(|t| t.is_type()) { 3 } else { 2 }
+    } else {
+        return Vec::new();
+    };
+
+    return token.sexpr().unwrap().get(index)
+        .and_then(|params| params.sexpr())
+        .map_or(Vec::new(), |params| params.iter().filter_map(|p| p.identifier_or_variadic()).collect());
 }
@@ -0,0 +1,226 @@
+use crate::{
+    token::*,
+    utils::{SourceInfo, Symbol},
+};
+
+// Validated s-expressions with their parts in named fields, see analyzer::lower.
+// Every node keeps the span of the tokens it was lowered from.
+
+#[derive(Debug, Clone, Copy)]
+pub struct Name {
+    pub name: Symbol,
+    pub si: SourceInfo,
+}
+
+#[derive(Debug, Clone)]
+pub enum Decl {
+    // {let name [a -> b] {fun {x} body...}}, also what {function ...} is rewritten into
+    Function {
+        name: Name,
+        t: Type,
+        params: Vec<Param>,
+        body: Vec<Expr>,
+        doc: Option<String>,
+        si: SourceInfo,
+    },
+
+    Procedure {
+        name: Name,
+        t: Type,
+        body: Vec<Expr>,
+        doc: Option<String>,
+        si: SourceInfo,
+    },
+
+    Let {
+        name: Name,
+        t: Type,
+        value: Expr,
+        doc: Option<String>,
+        si: SourceInfo,
+    },
+
+    // {struct Name {where {a Num}} {field Type}...}
+    Struct {
+        name: Name,
+        params: Vec<TypeParam>,
+        fields: Vec<Field>,
+        doc: Option<String>,
+        si: SourceInfo,
+    },
+
+    // {enum Name {where a} {Variant a}...}
+    Enum {
+        name: Name,
+        params: Vec<TypeParam>,
+        variants: Vec<Variant>,
+        doc: Option<String>,
+        si: SourceInfo,
+    },
+
+    // {import Std.Console {include println}} or {import-qualified Std.Network Net}
+    Import {
+        module: Vec<PathSegment>,
+        qualified: bool,
+        alias: Option<Name>,
+        filter: ImportFilter,
+        si: SourceInfo,
+    },
+
+    // {infixl + 5 infix-add}
+    Infix {
+        operator: Name,
+        associativity: Associativity,
+        precedence: u8,
+        function: Name,
+        si: SourceInfo,
+    },
+
+    // {macro name {params} body...}, the body is code that runs on code so it stays a token tree
+    Macro {
+        name: Name,
+        params: Vec<Param>,
+        body: Vec<Token>,
+        doc: Option<String>,
+        si: SourceInfo,
+    },
+
+    // Top level expression, see the notes in main.rs
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: Name,
+
+    // rest..
+    pub variadic: bool,
+}
+
+// Type variable of a struct or enum and the traits it has to implement
+#[derive(Debug, Clone)]
+pub struct TypeParam {
+    pub name: Name,
+    pub traits: Vec<Name>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: Name,
+    pub t: Type,
+    pub si: SourceInfo,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: Name,
+    pub fields: Vec<Type>,
+    pub si: SourceInfo,
+}
+
+#[derive(Debug, Clone)]
+pub enum ImportFilter {
+    All,
+    Include(Vec<Name>),
+    Exclude(Vec<Name>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Call {
+        function: Box<Expr>,
+        args: Vec<Arg>,
+        si: SourceInfo,
+    },
+
+    // {fun {x} body...}
+    Lambda {
+        params: Vec<Param>,
+        body: Vec<Expr>,
+        si: SourceInfo,
+    },
+
+    Literal(Literal, SourceInfo),
+    Identifier(Name),
+    Path(Vec<PathSegment>, SourceInfo),
+
+    // (a + b), kept as it was written until it is desugared into calls
+    Sweet(Vec<Expr>, SourceInfo),
+
+    // 'x and `x, quoted code stays a token tree since it is data
+    Quote {
+        quasi: bool,
+        token: Box<Token>,
+        si: SourceInfo,
+    },
+}
+
+// @label value or just value
+#[derive(Debug, Clone)]
+pub struct Arg {
+    pub label: Option<Name>,
+    pub value: Expr,
+}
+
+#[derive(Debug, Clone)]
+pub enum Literal {
+    // {} written as an expression
+    Unit,
+
+    UInt(u128, Option<IntType>),
+    Float(f64, Option<FloatType>),
+    String(String),
+    ByteString(Vec<u8>),
+    Char(char),
+    Byte(u8),
+}
+
+impl Expr {
+    pub fn si(&self) -> SourceInfo {
+        match self {
+            Expr::Call { si, .. } => *si,
+            Expr::Lambda { si, .. } => *si,
+            Expr::Literal(_, si) => *si,
+            Expr::Identifier(name) => name.si,
+            Expr::Path(_, si) => *si,
+            Expr::Sweet(_, si) => *si,
+            Expr::Quote { si, .. } => *si,
+        }
+    }
+}
+
+impl Decl {
+    pub fn si(&self) -> SourceInfo {
+        match self {
+            Decl::Function { si, .. } => *si,
+            Decl::Procedure { si, .. } => *si,
+            Decl::Let { si, .. } => *si,
+            Decl::Struct { si, .. } => *si,
+            Decl::Enum { si, .. } => *si,
+            Decl::Import { si, .. } => *si,
+            Decl::Infix { si, .. } => *si,
+            Decl::Macro { si, .. } => *si,
+            Decl::Expr(expr) => expr.si(),
+        }
+    }
+
+    // Name the declaration defines, imports, infix declarations and expressions don't define one
+    pub fn name(&self) -> Option<Name> {
+        match self {
+            Decl::Function { name, .. } => Some(*name),
+            Decl::Procedure { name, .. } => Some(*name),
+            Decl::Let { name, .. } => Some(*name),
+            Decl::Struct { name, .. } => Some(*name),
+            Decl::Enum { name, .. } => Some(*name),
+            Decl::Macro { name, .. } => Some(*name),
+            _ => None,
+        }
+    }
+}
@@ -4,3 +4,4 @@ pub mod analyzer;
 pub mod repl;
 pub mod program;
 pub mod token;
+pub mod ast;
@@ -10,7 +10,6 @@ use std::{
 
 // NOTE: Optimize everything later
 
-// TODO: Make a proper ast from S-Expressions
 // TODO: Improve error messages, add more metadata to ast and s-expressions.
 
 // Architecture
@@ -36,7 +35,7 @@ fn main() {
     let path = Path::new("test.xl");
     let path_text = path.display();
 
-    let mut file = match File::open(&path) {
+    let mut file = match File::open(path) {
         Err(e) => panic!("Couldn't open {}: {}", path_text, e),
         Ok(file) => file,
     };
@@ -3,12 +3,22 @@ use std::{
     ops::Range,
 };
 
-use crate::token::*;
+use crate::{
+    ast::{Associativity, ImportFilter},
+    token::*,
+    utils::*,
+};
 
 #[derive(Debug)]
 pub struct Program {
     modules: Vec<Module>,
     module_lookup: HashMap<String, usize>,
+
+    sources: SourceMap,
+    interner: Interner,
+
+    // Top level values in the order they have to be initialised in, see analyzer::init_order
+    init_order: Vec<Global>,
 }
 
 impl Program {
@@ -16,9 +26,38 @@ impl Program {
         Self {
             modules: Vec::new(),
             module_lookup: HashMap::new(),
+
+            sources: SourceMap::new(),
+            interner: Interner::new(),
+
+            init_order: Vec::new(),
         }
     }
 
+    pub fn add_source(&mut self, name: String, text: Vec<u8>) -> FileId {
+        self.sources.add_file(name, text)
+    }
+
+    pub fn get_sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    pub fn get_interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    pub fn get_interner_mut(&mut self) -> &mut Interner {
+        &mut self.interner
+    }
+
+    pub fn get_init_order(&self) -> &[Global] {
+        &self.init_order
+    }
+
+    pub fn set_init_order(&mut self, order: Vec<Global>) {
+        self.init_order = order;
+    }
+
     pub fn get_ids(&self) -> Range<usize> {
         0..self.modules.len()
     }
@@ -40,8 +79,18 @@ impl Program {
         &mut self.modules
     }
 
+    // For passes that rewrite code and still need to look up names
+    pub fn get_modules_mut_with_interner(&mut self) -> (&mut [Module], &Interner) {
+        (&mut self.modules, &self.interner)
+    }
+
+    // For passes that also make up new names
+    pub fn get_modules_mut_with_interner_mut(&mut self) -> (&mut [Module], &mut Interner) {
+        (&mut self.modules, &mut self.interner)
+    }
+
     pub fn get_module_id(&self, name: &String) -> Option<usize> {
-        Some(self.module_lookup.get(name)?.clone())
+        self.module_lookup.get(name).copied()
     }
 
     pub fn get_module_by_name(&self, name: &String) -> Option<&Module> {
@@ -67,12 +116,90 @@ pub struct Module {
     pub name: String,
     pub code: Vec<Token>,
 
-    pub variables: HashMap<String, usize>,
-    pub procedures: HashMap<String, usize>,
+    pub variables: HashMap<Symbol, usize>,
+    pub procedures: HashMap<Symbol, usize>,
+    pub structs: HashMap<Symbol, usize>,
+    pub enums: HashMap<Symbol, usize>,
+    pub macros: HashMap<Symbol, usize>,
+
+    // Constructors by their name, accessors and variants by the type they belong to
+    pub generated: HashMap<Symbol, Generated>,
+    pub members: HashMap<(Symbol, Symbol), Generated>,
+
+    pub imports: Vec<Import>,
+    pub operators: HashMap<Symbol, Operator>,
 
     pub expressions: Vec<usize>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Import {
+    // The module doesn't have to exist when the import is recorded, see resolve::check_imports
+    pub module: String,
+
+    // Names are only reachable through the module name or alias
+    pub qualified: bool,
+    pub alias: Option<Symbol>,
+    pub filter: ImportFilter,
+
+    // Span of the module name
+    pub si: SourceInfo,
+}
+
+impl Import {
+    pub fn allows(&self, name: Symbol) -> bool {
+        match &self.filter {
+            ImportFilter::All => true,
+            ImportFilter::Include(names) => names.iter().any(|n| n.name == name),
+            ImportFilter::Exclude(names) => !names.iter().any(|n| n.name == name),
+        }
+    }
+
+    // What qualified names from the imported module start with
+    pub fn prefix<'a>(&'a self, interner: &'a Interner) -> &'a str {
+        match self.alias {
+            Some(alias) => interner.resolve(alias),
+            None => &self.module,
+        }
+    }
+}
+
+// {infixl + 5 infix-add}
+#[derive(Debug, Clone, Copy)]
+pub struct Operator {
+    pub associativity: Associativity,
+    pub precedence: u8,
+    pub function: Symbol,
+    pub si: SourceInfo,
+}
+
+// Top level definition of a module by its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Global {
+    pub module: usize,
+    pub name: Symbol,
+}
+
+// Function or value that comes with a declaration instead of being written out
+#[derive(Debug, Clone)]
+pub struct Generated {
+    // Index of the declaration in code
+    pub index: usize,
+    pub kind: GeneratedKind,
+    pub t: Type,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratedKind {
+    Constructor,
+
+    // Index of the field it reads
+    Accessor(usize),
+
+    // Index of the variant it builds
+    Variant(usize),
+}
+
 impl Module {
     pub fn new(id: usize, name: String, code: Vec<Token>) -> Self {
         Self {
@@ -80,23 +207,62 @@ impl Module {
 
             variables: HashMap::new(),
             procedures: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            macros: HashMap::new(),
+
+            generated: HashMap::new(),
+            members: HashMap::new(),
+
+            imports: Vec::new(),
+            operators: HashMap::new(),
 
             expressions: Vec::new(),
         }
     }
 
+    // Used by the repl where defining a name again replaces the old definition, files report it
+    // as a duplicate in validate_module instead
     pub fn add_variable(&mut self, code: Token) {
-        let name = code.sexpr().unwrap().get(1).unwrap().identifier().unwrap().clone();
+        let name = code.sexpr().unwrap().get(1).unwrap().identifier().unwrap();
         self.code.push(code);
         self.variables.insert(name, self.code.len() - 1);
     }
 
     pub fn add_procedure(&mut self, code: Token) {
-        let name = code.sexpr().unwrap().get(1).unwrap().identifier().unwrap().clone();
+        let name = code.sexpr().unwrap().get(1).unwrap().identifier().unwrap();
         self.code.push(code);
         self.procedures.insert(name, self.code.len() - 1);
     }
 
+    pub fn add_macro(&mut self, code: Token) {
+        let name = code.sexpr().unwrap().get(1).unwrap().identifier().unwrap();
+        self.code.push(code);
+        self.macros.insert(name, self.code.len() - 1);
+    }
+
+    pub fn has_definition(&self, name: Symbol) -> bool {
+        self.variables.contains_key(&name) || self.procedures.contains_key(&name) || self.generated.contains_key(&name)
+    }
+
+    pub fn has_type(&self, name: Symbol) -> bool {
+        self.structs.contains_key(&name) || self.enums.contains_key(&name)
+    }
+
+    pub fn has_member(&self, owner: Symbol, name: Symbol) -> bool {
+        self.members.contains_key(&(owner, name))
+    }
+
+    // Documentation of a top level definition from the doc comments written above it
+    pub fn get_doc(&self, name: Symbol) -> Option<&str> {
+        let index = self.variables.get(&name)
+            .or_else(|| self.procedures.get(&name))
+            .or_else(|| self.structs.get(&name))
+            .or_else(|| self.enums.get(&name))
+            .or_else(|| self.macros.get(&name))?;
+        self.code.get(*index)?.doc.as_deref()
+    }
+
     pub fn add_expression(&mut self, code: Token) {
         self.code.push(code);
         self.expressions.push(self.code.len() - 1);
@@ -2,7 +2,8 @@ use crate::{
     tokenizer::tokenize,
     program::*,
     analyzer::{
-        syntax,
+        macros::expand_code,
+        syntax::{self, bind_variadic_calls, define_type, define_import, define_operator, desugar_sweet_exprs, variadic_function, visible_operators, visible_variadic_functions},
         utils::*,
     },
 };
@@ -20,7 +21,6 @@ pub fn repl() {
     let mut program = Program::new();
 
     let module_id = program.new_module("repl".to_string(), Vec::new());
-    let module = program.get_module_by_id_mut(module_id).unwrap();
 
     loop {
         input.clear();
@@ -29,39 +29,82 @@ pub fn repl() {
         let _ = io::stdout().flush();
         let _ = io::stdin().read_line(&mut input);
 
-        if input == ":exit\r\n".to_string() {
+        if input == ":exit\r\n" {
             break;
         }
 
-        let lex_result = tokenize(input.as_bytes());
-        if lex_result.is_err() {
-            let errors = lex_result.unwrap_err();
-            for e in &errors {
-                println!("{}", e.to_string("repl"))
+        if let Some(name) = input.trim().strip_prefix(":doc ") {
+            let module = program.get_module_by_id(module_id).unwrap();
+            let doc = program.get_interner().get(name.trim()).and_then(|name| module.get_doc(name));
+
+            match doc {
+                Some(doc) => println!("{doc}"),
+                None => println!("No documentation for '{}'", name.trim()),
             }
 
             continue;
         }
 
-        let mut tokens = lex_result.unwrap();
+        // Every input is its own file so errors can still show the line they came from
+        let file = program.add_source("repl".to_string(), input.as_bytes().to_vec());
+
+        let mut tokens = match tokenize(input.as_bytes(), file, program.get_interner_mut()) {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                for e in &errors {
+                    println!("{}", e.report(program.get_sources()))
+                }
+
+                continue;
+            },
+        };
+
+        let (modules, interner) = program.get_modules_mut_with_interner_mut();
+        let mut errors = expand_code(&mut tokens, &modules[module_id].code, interner);
+
+        errors.extend(syntax::validate_code(&mut tokens, program.get_interner()));
+
+        // Operators declared in this input can be used from the next one on
+        let operators = visible_operators(&program, module_id);
+        desugar_sweet_exprs(&mut tokens, &operators, program.get_interner(), &mut errors);
+
+        // Variadic functions can be called in the input they are defined in
+        let mut functions = visible_variadic_functions(&program, module_id);
+        functions.extend(tokens.iter().filter_map(variadic_function));
+        bind_variadic_calls(&mut tokens, &functions, program.get_interner(), &mut errors);
+
+        for e in &errors {
+            println!("{}", e.report(program.get_sources()))
+        }
+
+        let (modules, interner) = program.get_modules_mut_with_interner();
+        let module = &mut modules[module_id];
 
-        let errors = syntax::validate_code(&mut tokens);
         if errors.len() == 0 {
             for token in tokens {
                 if is_variable(&token) {
                     module.add_variable(token);
                 } else if is_procedure(&token) {
                     module.add_procedure(token);
+                } else if is_struct(&token) || is_enum(&token) {
+                    module.code.push(token);
+                    define_type(module, module.code.len() - 1, interner);
+                } else if is_import(&token) {
+                    module.code.push(token);
+                    define_import(module, module.code.len() - 1, interner);
+                } else if is_macro(&token) {
+                    module.add_macro(token);
+                } else if is_infix(&token) {
+                    module.code.push(token);
+                    define_operator(module, module.code.len() - 1);
                 } else {
                     module.add_expression(token);
                 }
             }
         }
 
-        println!("{:?}", errors);
-
         for var in &module.code {
-            print!("{}\n\n", var.to_string());
+            print!("{}\n\n", var.to_string(interner));
         }
      }
 }
@@ -1,9 +1,79 @@
-use crate::utils::SourceInfo;
+use crate::utils::{SourceInfo, Symbol, Interner};
 
+use std::fmt;
+
+// Token trees own their lists instead of being ids into an arena, macros, sweet expressions and
+// variadic calls rewrite them in place which is simplest on owned lists. Identifiers in them are
+// interned so copying or comparing one doesn't allocate.
 #[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub si: SourceInfo,
+
+    // Text of the doc comments right before the token
+    pub doc: Option<String>,
+
+    // Only set by the lossless tokenizer
+    pub trivia: Option<Box<TokenTrivia>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TokenTrivia {
+    pub leading: Vec<Trivia>,
+
+    // The exact source text of a literal or identifier, lists are written out from their children
+    pub source: Vec<u8>,
+
+    // Trivia between the last element of a list and its closing character
+    pub closing: Vec<Trivia>,
+
+    // Lists read from 'x style shorthand, their children are written out without brackets
+    pub shorthand: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+
+    // #| line or #-| block -#
+    DocComment,
+}
+
+#[derive(Debug, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: Vec<u8>,
+    pub si: SourceInfo,
+}
+
+// Output of the lossless tokenizer, printing it gives back the original source
+#[derive(Debug, Clone)]
+pub struct SyntaxTree {
+    pub tokens: Vec<Token>,
+
+    // Trivia after the last token in the file
+    pub trailing: Vec<Trivia>,
+}
+
+impl SyntaxTree {
+    pub fn to_source(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+
+        for token in &self.tokens {
+            token.write_source(&mut result);
+        }
+
+        write_trivia(&self.trailing, &mut result);
+        return result;
+    }
+}
+
+fn write_trivia(trivia: &[Trivia], acc: &mut Vec<u8>) {
+    for t in trivia {
+        acc.extend_from_slice(&t.text);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -13,22 +83,90 @@ pub enum TokenKind {
 
     TypeExpr(Type),
 
-    Identifier(String),
+    Identifier(Symbol),
+
+    // Qualified name like Std.Console or Gender.Female, always has more than one segment
+    Path(Vec<PathSegment>),
+
+    // Variadic parameter rest..
+    Variadic(Symbol),
+
+    // Labelled argument @name
+    Label(Symbol),
     
-    // Untyped integer literals
-    Int(i128),
-    UInt(u128),
+    // Integer literals, the type is only known if the literal has a suffix like 42i32
+    UInt(u128, Option<IntType>),
+
+    // Float literal, the type is only known if the literal has a suffix like 1.5f32
+    Float(f64, Option<FloatType>),
+
+    // String literal, always valid UTF-8
+    String(String),
 
-    // Untyped float literal
-    Float(f64),
+    // Byte string literal b"..."
+    ByteString(Vec<u8>),
 
-    // String literal
-    String(Vec<u8>),
+    // Character literal 'a'
+    Char(char),
+
+    // Byte literal b'a'
+    Byte(u8),
 }
 
 // TODO: Maybe I should make some of these functions return an option instead of crashing
 // TODO: Make some function that allow you to check if an s expression is a function
 impl Token {
+    pub fn new(kind: TokenKind, si: SourceInfo) -> Self {
+        Self {
+            kind,
+            si,
+            doc: None,
+            trivia: None,
+        }
+    }
+
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc;
+        return self;
+    }
+
+    // Writes the token back out as source code, only tokens from the lossless tokenizer have
+    // anything to write
+    pub fn write_source(&self, acc: &mut Vec<u8>) {
+        let trivia = match &self.trivia {
+            Some(trivia) => trivia,
+            None => return,
+        };
+
+        write_trivia(&trivia.leading, acc);
+
+        let (open, close, list) = match &self.kind {
+            TokenKind::SExpr(list) => (b'{', b'}', list),
+            TokenKind::SweetExpr(list) => (b'(', b')', list),
+
+            _ => {
+                acc.extend_from_slice(&trivia.source);
+                return;
+            },
+        };
+
+        if trivia.shorthand {
+            for token in list {
+                token.write_source(acc);
+            }
+
+            return;
+        }
+
+        acc.push(open);
+        for token in list {
+            token.write_source(acc);
+        }
+
+        write_trivia(&trivia.closing, acc);
+        acc.push(close);
+    }
+
     pub fn sexpr(&self) -> Option<&[Token]> {
         match &self.kind {
             TokenKind::SExpr(sexp) => Some(sexp),
@@ -50,6 +188,13 @@ impl Token {
         }
     }
 
+    pub fn is_sweet_expr(&self) -> bool {
+        match &self.kind {
+            TokenKind::SweetExpr(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn is_identifier(&self) -> bool {
         match &self.kind {
             TokenKind::Identifier(_) => true,
@@ -64,45 +209,84 @@ impl Token {
         }
     }
 
-    pub fn match_identifier(&self, s: &str) -> bool {
+    pub fn is_path(&self) -> bool {
+        match &self.kind {
+            TokenKind::Path(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn path(&self) -> Option<&[PathSegment]> {
+        match &self.kind {
+            TokenKind::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn is_variadic(&self) -> bool {
+        match &self.kind {
+            TokenKind::Variadic(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_label(&self) -> bool {
+        match &self.kind {
+            TokenKind::Label(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn match_identifier(&self, s: Symbol) -> bool {
         match &self.kind {
-            TokenKind::Identifier(name) => name == s,
+            TokenKind::Identifier(name) => *name == s,
             _ => false,
         }
     }
 
-    pub fn match_first_identifier(&self, s: &str) -> bool {
+    pub fn match_first_identifier(&self, s: Symbol) -> bool {
         self.match_n_identifier(s, 0)
     }
 
-    pub fn match_n_identifier(&self, s: &str, index: usize) -> bool {
+    pub fn match_n_identifier(&self, s: Symbol, index: usize) -> bool {
         if let TokenKind::SExpr(sexp) = &self.kind {
             if let Some(token) = sexp.get(index) {
-                if let TokenKind::Identifier(id) = &token.kind {
-                    return id == s;
-                }
+                return token.match_identifier(s);
             }
         }
 
         return false;
     }
 
-    pub fn identifier(&self) -> Option<&String> {
+    pub fn identifier(&self) -> Option<Symbol> {
         match &self.kind {
-            TokenKind::Identifier(id) => Some(id),
+            TokenKind::Identifier(id) => Some(*id),
             _ => None,
         }
     }
 
-    pub fn to_string(&self) -> String {
-        fn token_to_string(token: &Token, acc: &mut String, indentation: i32) {
+    // Name of a parameter, variadic or not
+    pub fn identifier_or_variadic(&self) -> Option<Symbol> {
+        match &self.kind {
+            TokenKind::Identifier(id) | TokenKind::Variadic(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    pub fn to_string(&self, interner: &Interner) -> String {
+        fn token_to_string(token: &Token, interner: &Interner, acc: &mut String, indentation: i32) {
             match &token.kind {
-                TokenKind::Identifier(v) => acc.push_str(&format!("Identifier({}) ", v)),
-                TokenKind::String(v) => acc.push_str(&format!("String(\"{}\") ", unsafe { String::from_utf8_unchecked(v.to_vec()) })),
-                TokenKind::Int(v) => acc.push_str(&format!("Int({}) ", v)),
-                TokenKind::UInt(v) => acc.push_str(&format!("UInt({}) ", v)),
-                TokenKind::Float(v) => acc.push_str(&format!("Float({}) ", v)),
-                TokenKind::SExpr(v) => {
+                TokenKind::Identifier(v) => acc.push_str(&format!("Identifier({}) ", interner.resolve(*v))),
+                TokenKind::Path(v) => acc.push_str(&format!("Path({}) ", path_to_string(v, interner))),
+                TokenKind::Variadic(v) => acc.push_str(&format!("Variadic({}) ", interner.resolve(*v))),
+                TokenKind::Label(v) => acc.push_str(&format!("Label({}) ", interner.resolve(*v))),
+                TokenKind::String(v) => acc.push_str(&format!("String({:?}) ", v)),
+                TokenKind::ByteString(v) => acc.push_str(&format!("ByteString(b\"{}\") ", v.escape_ascii())),
+                TokenKind::Char(v) => acc.push_str(&format!("Char({:?}) ", v)),
+                TokenKind::Byte(v) => acc.push_str(&format!("Byte(b'{}') ", v.escape_ascii())),
+                TokenKind::UInt(v, t) => acc.push_str(&format!("UInt({}{}) ", v, suffix_to_string(t))),
+                TokenKind::Float(v, t) => acc.push_str(&format!("Float({}{}) ", v, suffix_to_string(t))),
+                TokenKind::SExpr(v) | TokenKind::SweetExpr(v) => {
                     if indentation > 0 {
                         acc.push('\n');
                     }
@@ -111,32 +295,99 @@ impl Token {
                         acc.push_str("  ");
                     }
 
-                    acc.push_str("SExpr( ");
+                    if token.is_sexpr() {
+                        acc.push_str("SExpr( ");
+                    } else {
+                        acc.push_str("SweetExpr( ");
+                    }
                     
                     for t in v {
-                        token_to_string(t, acc, indentation + 1);
+                        token_to_string(t, interner, acc, indentation + 1);
                     }
 
                     acc.push(')');
                 },
 
-                TokenKind::TypeExpr(v) => {
-                    match v {
-                        Type::Unknown => acc.push_str("TypeExpr(Unknown) "),
-                        _ => {},
-                    }
-                }
-
-                _ => {},
+                TokenKind::TypeExpr(v) => acc.push_str(&format!("TypeExpr({}) ", v)),
             }
         }
 
+        fn suffix_to_string<T: fmt::Display>(suffix: &Option<T>) -> String {
+            suffix.as_ref().map_or(String::new(), |s| s.to_string())
+        }
+
         let mut result = String::new();
-        token_to_string(self, &mut result, 0);
+        token_to_string(self, interner, &mut result, 0);
         return result;
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct PathSegment {
+    pub name: Symbol,
+    pub si: SourceInfo,
+}
+
+pub fn path_to_string(path: &[PathSegment], interner: &Interner) -> String {
+    path.iter().map(|s| interner.resolve(s.name)).collect::<Vec<_>>().join(".")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntType {
+    I8, I16, I32, I64, I128,
+    U8, U16, U32, U64, U128,
+}
+
+impl IntType {
+    pub fn max(self) -> u128 {
+        match self {
+            IntType::I8 => i8::MAX as u128,
+            IntType::I16 => i16::MAX as u128,
+            IntType::I32 => i32::MAX as u128,
+            IntType::I64 => i64::MAX as u128,
+            IntType::I128 => i128::MAX as u128,
+            IntType::U8 => u8::MAX as u128,
+            IntType::U16 => u16::MAX as u128,
+            IntType::U32 => u32::MAX as u128,
+            IntType::U64 => u64::MAX as u128,
+            IntType::U128 => u128::MAX,
+        }
+    }
+}
+
+impl fmt::Display for IntType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            IntType::I8 => "i8",
+            IntType::I16 => "i16",
+            IntType::I32 => "i32",
+            IntType::I64 => "i64",
+            IntType::I128 => "i128",
+            IntType::U8 => "u8",
+            IntType::U16 => "u16",
+            IntType::U32 => "u32",
+            IntType::U64 => "u64",
+            IntType::U128 => "u128",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatType {
+    F32, F64,
+}
+
+impl fmt::Display for FloatType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FloatType::F32 => write!(f, "f32"),
+            FloatType::F64 => write!(f, "f64"),
+        }
+    }
+}
+
 // [Int]
 // [Int, Int -> int]
 // [List Int]
@@ -145,8 +396,11 @@ impl Token {
 // [Either a b]
 // [a, b -> c]
 // [Num a, Eq a b => a, b -> c]
+// [a Num Eq, b Num => a, b -> c]
+// [[a -> b], List a -> List b]
+// [Num a => a, a, a.. -> a]
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Unknown,
 
@@ -164,6 +418,136 @@ pub enum Type {
 
     Function {
         params: Vec<Type>,
+
+        // Type of each extra argument for a variadic function
+        variadic: Option<Box<Type>>,
+
         return_type: Box<Type>,
     },
 }
+
+impl Type {
+    pub fn contains_variable(&self, variable: &str) -> bool {
+        match self {
+            Type::Generic { name, .. } => name == variable,
+            Type::Complex { params, .. } => params.iter().any(|p| p.contains_variable(variable)),
+            Type::Function { params, variadic, return_type } => {
+                params.iter().any(|p| p.contains_variable(variable)) ||
+                variadic.as_ref().is_some_and(|v| v.contains_variable(variable)) ||
+                return_type.contains_variable(variable)
+            },
+
+            _ => false,
+        }
+    }
+
+    // Every type variable in the type, each one once
+    pub fn variables(&self) -> Vec<&str> {
+        let mut acc = Vec::new();
+        self.collect_variables(&mut acc);
+
+        return acc;
+    }
+
+    fn collect_variables<'a>(&'a self, acc: &mut Vec<&'a str>) {
+        match self {
+            Type::Generic { name, .. } if !acc.contains(&name.as_str()) => acc.push(name),
+
+            Type::Complex { params, .. } => params.iter().for_each(|p| p.collect_variables(acc)),
+            Type::Function { params, variadic, return_type } => {
+                params.iter().chain(variadic.as_deref()).for_each(|p| p.collect_variables(acc));
+                return_type.collect_variables(acc);
+            },
+
+            _ => {},
+        }
+    }
+
+    // Type variables only have traits if the type expression had a '=>' in it
+    pub fn has_constraints(&self) -> bool {
+        match self {
+            Type::Generic { traits, .. } => !traits.is_empty(),
+            Type::Complex { params, .. } => params.iter().any(|p| p.has_constraints()),
+            Type::Function { params, variadic, return_type } => {
+                params.iter().chain(variadic.as_deref()).any(|p| p.has_constraints()) ||
+                return_type.has_constraints()
+            },
+
+            _ => false,
+        }
+    }
+
+    // Adds traits to every use of the type variable that doesn't have them yet
+    pub fn add_traits(&mut self, variable: &str, new_traits: &[String]) {
+        match self {
+            Type::Generic { name, traits } if name == variable => {
+                for t in new_traits {
+                    if !traits.contains(t) {
+                        traits.push(t.clone());
+                    }
+                }
+            },
+
+            Type::Complex { params, .. } => params.iter_mut().for_each(|p| p.add_traits(variable, new_traits)),
+            Type::Function { params, variadic, return_type } => {
+                params.iter_mut().chain(variadic.as_deref_mut()).for_each(|p| p.add_traits(variable, new_traits));
+                return_type.add_traits(variable, new_traits);
+            },
+
+            _ => {},
+        }
+    }
+}
+
+// Lower case names in types are type variables, the rest name concrete types
+pub fn is_type_variable(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_lowercase() || c == '_')
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_param(t: &Type, f: &mut fmt::Formatter) -> fmt::Result {
+            match t {
+                Type::Complex { .. } | Type::Function { .. } => write!(f, "[{t}]"),
+                _ => write!(f, "{t}"),
+            }
+        }
+
+        match self {
+            Type::Unknown => write!(f, "Unknown"),
+            Type::Simple(name) => write!(f, "{name}"),
+
+            Type::Generic { name, traits } if traits.is_empty() => write!(f, "{name}"),
+            Type::Generic { name, traits } => write!(f, "{name}<{}>", traits.join(", ")),
+
+            Type::Complex { name, params } => {
+                write!(f, "{name}")?;
+                for p in params {
+                    write!(f, " ")?;
+                    write_param(p, f)?;
+                }
+
+                Ok(())
+            },
+
+            Type::Function { params, variadic, return_type } => {
+                for (i, p) in params.iter().chain(variadic.as_deref()).enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    match p {
+                        Type::Function { .. } => write!(f, "[{p}]")?,
+                        _ => write!(f, "{p}")?,
+                    }
+                }
+
+                if variadic.is_some() {
+                    write!(f, "..")?;
+                }
+
+                write!(f, " -> {return_type}")
+            },
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use std::ops::Range;
+
+use crate::{
+    utils::*,
+    token::*,
+};
+
+use super::{
+    scanner::Scanner,
+    scan_top_level,
+    skip_whitespace,
+};
+
+// Scanning a token reads at most one character past its end, like a name peeking at what follows
+// it or checking for '..'. That character is up to 4 bytes of UTF-8, so tokens that end this many
+// bytes before an edit are scanned again too
+const LOOKAHEAD: usize = 4;
+
+// Replaces the bytes in range with replacement, the range is in the text before the edit
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: Vec<u8>,
+}
+
+impl TextEdit {
+    pub fn apply(&self, text: &[u8]) -> Vec<u8> {
+        let mut result = Vec::with_capacity(text.len() - self.range.len() + self.replacement.len());
+
+        result.extend_from_slice(&text[..self.range.start]);
+        result.extend_from_slice(&self.replacement);
+        result.extend_from_slice(&text[self.range.end..]);
+
+        return result;
+    }
+}
+
+// Tokenizes text after edit has been applied to it, tokens has to be the result of tokenize on
+// text and is reused for the result. Only the top level tokens around the edit are scanned again,
+// scanning stops as soon as it lines up with an old token after the edit and every token from
+// there on is reused with its position moved. The result is always the same as tokenizing the new
+// text from scratch.
+pub fn retokenize(
+    text: &[u8],
+    mut tokens: Vec<Token>,
+    edit: &TextEdit,
+    file: FileId,
+    interner: &mut Interner,
+) -> Result<Vec<Token>, Vec<Error>> {
+    let edit = &align_to_chars(text, edit);
+    let new_text = edit.apply(text);
+
+    let first = tokens.partition_point(|t| t.si.end_index + LOOKAHEAD < edit.range.start);
+    let start = match first.checked_sub(1) {
+        Some(i) => tokens[i].si.after(),
+        None => SourceInfo::new(file, 1, 1, 0),
+    };
+
+    let shift = Shift {
+        old_end: position(text, start, edit.range.end, file, interner),
+        new_end: position(&new_text, start, edit.range.start + edit.replacement.len(), file, interner),
+    };
+
+    let mut scanned = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut scanner = Scanner::new(&new_text, file, interner);
+    scanner.seek(start.index, start.line, start.column);
+
+    // End of whatever was scanned last, the text between it and the next token decides its docs
+    let mut boundary = scanner.index;
+    skip_whitespace(&mut scanner, &mut errors);
+
+    let mut unchanged = tokens.len();
+
+    while !scanner.is_at_end() {
+        if let Some(next) = shift.find_unchanged(&tokens, boundary, scanner.index) {
+            unchanged = next;
+            break;
+        }
+
+        if let Some(token) = scan_top_level(&mut scanner, &mut errors) {
+            scanned.push(token);
+        }
+
+        boundary = scanner.index;
+        skip_whitespace(&mut scanner, &mut errors);
+    }
+
+    if errors.len() > 0 {
+        return Err(errors);
+    }
+
+    for token in &mut tokens[unchanged..] {
+        shift.token(token);
+    }
+
+    tokens.splice(first..unchanged, scanned);
+    return Ok(tokens);
+}
+
+// An edit can start or end in the middle of a multi byte character, it is grown to whole
+// characters so positions before and after it can be found by scanning
+fn align_to_chars(text: &[u8], edit: &TextEdit) -> TextEdit {
+    let is_continuation = |i: usize| text.get(i).is_some_and(|b| b & 0xC0 == 0x80);
+
+    let mut start = edit.range.start;
+    while start > 0 && is_continuation(start) {
+        start -= 1;
+    }
+
+    let mut end = edit.range.end;
+    while is_continuation(end) {
+        end += 1;
+    }
+
+    let mut replacement = text[start..edit.range.start].to_vec();
+    replacement.extend_from_slice(&edit.replacement);
+    replacement.extend_from_slice(&text[edit.range.end..end]);
+
+    return TextEdit {
+        range: start..end,
+        replacement,
+    };
+}
+
+// Line and column of index, counted the same way the scanner does
+fn position(text: &[u8], start: SourceInfo, index: usize, file: FileId, interner: &mut Interner) -> SourceInfo {
+    let mut scanner = Scanner::new(text, file, interner);
+    scanner.seek(start.index, start.line, start.column);
+
+    while scanner.index < index && !scanner.is_at_end() {
+        scanner.advance();
+    }
+
+    return scanner.get_source_info();
+}
+
+// Moves positions after the edit to where they are in the new text
+struct Shift {
+    old_end: SourceInfo,
+    new_end: SourceInfo,
+}
+
+impl Shift {
+    fn index(&self, index: usize) -> usize {
+        index - self.old_end.index + self.new_end.index
+    }
+
+    // Only the rest of the line the edit ends on moves sideways
+    fn line_column(&self, line: i64, column: i64) -> (i64, i64) {
+        let new_line = line - self.old_end.line + self.new_end.line;
+
+        if line == self.old_end.line {
+            return (new_line, column - self.old_end.column + self.new_end.column);
+        }
+
+        return (new_line, column);
+    }
+
+    fn si(&self, si: SourceInfo) -> SourceInfo {
+        let (line, column) = self.line_column(si.line, si.column);
+        let (end_line, end_column) = self.line_column(si.end_line, si.end_column);
+
+        return SourceInfo {
+            file: si.file,
+            line, column,
+            index: self.index(si.index),
+            end_line, end_column,
+            end_index: self.index(si.end_index),
+        };
+    }
+
+    // The old token that starts at new_index if the text before it since boundary is the same as
+    // it was, from there on the old tokens are still correct
+    fn find_unchanged(&self, tokens: &[Token], boundary: usize, new_index: usize) -> Option<usize> {
+        if boundary < self.new_end.index {
+            return None;
+        }
+
+        let old_index = new_index - self.new_end.index + self.old_end.index;
+        let old_boundary = boundary - self.new_end.index + self.old_end.index;
+
+        let next = tokens.partition_point(|t| t.si.index < old_index);
+        if next == 0 || next == tokens.len() || tokens[next].si.index != old_index {
+            return None;
+        }
+
+        if tokens[next - 1].si.end_index != old_boundary {
+            return None;
+        }
+
+        return Some(next);
+    }
+
+    fn token(&self, token: &mut Token) {
+        token.si = self.si(token.si);
+
+        if let Some(trivia) = &mut token.trivia {
+            for t in trivia.leading.iter_mut().chain(trivia.closing.iter_mut()) {
+                t.si = self.si(t.si);
+            }
+        }
+
+        match &mut token.kind {
+            TokenKind::SExpr(list) | TokenKind::SweetExpr(list) => {
+                for token in list {
+                    self.token(token);
+                }
+            },
+
+            TokenKind::Path(segments) => {
+                for segment in segments {
+                    segment.si = self.si(segment.si);
+                }
+            },
+
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize;
+
+    const SAMPLE: &str = r##"# A comment
+## Adds two numbers
+{function add [Num a => a, a -> a] {x y}
+  {+ x y}}
+
+{let values [List Int] {list 1 0x1F 2.5e3 4 1_000u16}}
+{let text "tab\t and \u{e9}" }
+{let raw r#"raw "string""#}
+{let chars {list 'a' '\n' b'z' 'é'}}
+{let sweet (1 + 2 * {add 3 4})}
+{let quoted `{a ,b ,@c}}
+{let named {make-person @name "Lyra" @age 24 @gender Gender.Female}}
+{procedure main [Unit]
+  {println "héllo wörld"}}
+"##;
+
+    // Pieces of code edits are made of, mostly things that change how the text around them is read
+    const PIECES: [&str; 20] = [
+        "{", "}", "(", ")", "[", "]", "\"", "'", "#", "\n", " ", "x", "1", ".5", "e", "->", "é", "`", ",", "{let y 2}\n",
+    ];
+
+    // xorshift, the tests have to be the same every time they run
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            return self.0;
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    fn random_edit(rng: &mut Rng, text: &[u8]) -> TextEdit {
+        let start = rng.below(text.len() + 1);
+        let end = (start + rng.below(6)).min(text.len());
+
+        let mut replacement = Vec::new();
+        for _ in 0..rng.below(3) {
+            replacement.extend_from_slice(PIECES[rng.below(PIECES.len())].as_bytes());
+        }
+
+        return TextEdit {
+            range: start..end,
+            replacement,
+        };
+    }
+
+    // Tokens, their spans and the errors have to be exactly what tokenizing from scratch gives
+    #[test]
+    fn retokenize_matches_tokenize() {
+        let mut rng = Rng(0x2545F4914F6CDD1D);
+        let mut interner = Interner::new();
+        let file = FileId(0);
+
+        let mut text = SAMPLE.as_bytes().to_vec();
+        let mut tokens = tokenize(&text, file, &mut interner).unwrap();
+
+        for _ in 0..5000 {
+            let edit = random_edit(&mut rng, &text);
+            let new_text = edit.apply(&text);
+
+            let expected = tokenize(&new_text, file, &mut interner);
+            let result = retokenize(&text, tokens.clone(), &edit, file, &mut interner);
+
+            assert_eq!(
+                format!("{result:?}"),
+                format!("{expected:?}"),
+                "edit {edit:?} on {:?}",
+                String::from_utf8_lossy(&text),
+            );
+
+            // Edits keep building on each other while the text still tokenizes
+            match expected {
+                Ok(new_tokens) => {
+                    text = new_text;
+                    tokens = new_tokens;
+                },
+
+                Err(_) if rng.below(4) == 0 => {
+                    text = SAMPLE.as_bytes().to_vec();
+                    tokens = tokenize(&text, file, &mut interner).unwrap();
+                },
+
+                Err(_) => {},
+            }
+        }
+    }
+}
@@ -3,229 +3,1071 @@ use crate::{
     token::*,
 };
 
-use std::{
-    str::FromStr,
-    num::IntErrorKind,
-};
+use std::str::FromStr;
+use unicode_ident::{is_xid_start, is_xid_continue};
+
+mod scanner;
+use scanner::Scanner;
+
+mod incremental;
+pub use incremental::*;
+
+mod type_expr;
+use type_expr::scan_type;
+
+// TODO: Clean this up
+
+// Every error in the file is reported, after an error inside of an s-expression we skip to the
+// '}' that closes it or to the next s-expression that starts at the beginning of a line.
+pub fn tokenize(text: &[u8], file: FileId, interner: &mut Interner) -> Result<Vec<Token>, Vec<Error>> {
+    let mut scanner = Scanner::new(text, file, interner);
+    return scan_tokens(&mut scanner);
+}
+
+// Keeps whitespace and comments as trivia on the tokens so the source can be written back out
+// exactly as it was.
+pub fn tokenize_lossless(text: &[u8], file: FileId, interner: &mut Interner) -> Result<SyntaxTree, Vec<Error>> {
+    let mut scanner = Scanner::new(text, file, interner);
+    scanner.trivia = Some(Vec::new());
+
+    let tokens = scan_tokens(&mut scanner)?;
+
+    return Ok(SyntaxTree {
+        tokens,
+        trailing: scanner.take_trivia(),
+    });
+}
+
+fn scan_tokens(scanner: &mut Scanner) -> Result<Vec<Token>, Vec<Error>> {
+    let mut errors: Vec<Error> = Vec::new();
+    let mut tokens: Vec<Token> = Vec::new();
+
+    skip_whitespace(scanner, &mut errors);
+    while !scanner.is_at_end() {
+        if let Some(token) = scan_top_level(scanner, &mut errors) {
+            tokens.push(token);
+        }
+
+        skip_whitespace(scanner, &mut errors);
+    }
+
+    scanner.take_doc();
+
+    return if errors.len() > 0 { Err(errors) } else { Ok(tokens) };
+}
+
+fn scan_top_level(scanner: &mut Scanner, errors: &mut Vec<Error>) -> Option<Token> {
+    if is_closing_char(scanner.peek()) {
+        errors.push(unexpected_char(scanner));
+        return None;
+    }
+
+    let token = scan_token(scanner, errors)?;
+
+    if token.is_type() {
+        errors.push(Error {
+            message: "Type expressions can not be written at the top level".to_string(),
+            si: token.si,
+        });
+        return None;
+    }
+
+    return Some(token);
+}
+
+fn scan_token(scanner: &mut Scanner, errors: &mut Vec<Error>) -> Option<Token> {
+    let si = scanner.get_source_info();
+    let leading = scanner.take_trivia();
+    let doc = scanner.take_doc();
+    let c = scanner.peek();
+
+    let result = match c {
+        '{' => return scan_sexpr(scanner, errors).map(|t| with_trivia(scanner, t.with_doc(doc), leading)),
+        '(' => return scan_sweet_expr(scanner, errors).map(|t| with_trivia(scanner, t.with_doc(doc), leading)),
+
+        'b' if scanner.match_string("b\"") || scanner.match_string("br\"") || scanner.match_string("br#") => scan_string(scanner),
+        'r' if scanner.match_string("r\"") || scanner.match_string("r#") => scan_string(scanner),
+
+        '[' => scan_type(scanner),
+
+        '\'' | '`' | ',' if !is_char_literal(scanner) => return scan_quote(scanner, errors).map(|t| with_trivia(scanner, t.with_doc(doc), leading)),
+
+        '\'' => scan_char(scanner),
+        'b' if scanner.match_string("b'") => scan_char(scanner),
+
+        '@' => scan_label(scanner),
+
+        'a'..='z'|'A'..='Z'|
+        '!'|'$'..='&'|'*'|'+'|
+        '-'|'/'|':'..='@'|'\\'|
+        '^'|'_'|'|'|'~' => scan_identifier(scanner),
+
+        '0'..='9' => scan_number(scanner),
+
+        c if is_xid_start(c) => scan_identifier(scanner),
+
+        '"' => scan_string(scanner),
+
+        _ => Err(unexpected_char(scanner)),
+    };
+
+    match result {
+        Ok(mut token) => {
+            token.si = scanner.span_from(si);
+
+            // Comments inside of a type expression are part of its source text
+            scanner.take_trivia();
+            scanner.take_doc();
+
+            return Some(with_trivia(scanner, token.with_doc(doc), leading));
+        },
+        Err(error) => {
+            errors.push(error);
+            return None;
+        },
+    }
+}
+
+fn with_trivia(scanner: &mut Scanner, mut token: Token, leading: Vec<Trivia>) -> Token {
+    if scanner.trivia.is_none() {
+        return token;
+    }
+
+    let is_list = token.is_sexpr() || token.is_sweet_expr();
+    let si = token.si;
+
+    let trivia = token.trivia.get_or_insert_with(Box::default);
+    trivia.leading = leading;
+
+    if !is_list {
+        trivia.source = scanner.text[si.index..si.end_index].to_vec();
+    }
+
+    return token;
+}
+
+fn unexpected_char(scanner: &mut Scanner) -> Error {
+    let si = scanner.get_source_info();
+    let c = scanner.advance();
+
+    return Error::new(
+        format!("Unexpected character '{}'", display_char(c)),
+        scanner.span_from(si),
+    );
+}
+
+// Control characters and the like are escaped, anything outside of ascii is shown as it is written
+pub(super) fn display_char(c: char) -> String {
+    if c.is_ascii() {
+        return c.escape_default().to_string();
+    }
+
+    return c.to_string();
+}
+
+fn is_closing_char(c: char) -> bool {
+    c == '}' || c == ')' || c == ']'
+}
+
+// An s-expression at the start of a line is most likely a new top level form
+fn is_top_level_start(scanner: &Scanner) -> bool {
+    scanner.match_char('{') && scanner.get_source_info().column == 1
+}
+
+// Skips to the unmatched closing character that ends the current list, or to the next top level
+// s-expression
+pub(super) fn synchronize(scanner: &mut Scanner) {
+    let mut depth = 0;
+
+    while !scanner.is_at_end() && !is_top_level_start(scanner) {
+        let c = scanner.peek();
+
+        match c {
+            '"' => {
+                let _ = scan_string(scanner);
+                continue;
+            },
+
+            '#' => {
+                skip_whitespace(scanner, &mut Vec::new());
+                continue;
+            },
+
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' if depth == 0 => return,
+            '}' | ')' | ']' => depth -= 1,
+            _ => {},
+        }
+
+        scanner.advance();
+    }
+}
+
+fn scan_sexpr(scanner: &mut Scanner, errors: &mut Vec<Error>) -> Option<Token> {
+    let sexpr_si = scanner.get_source_info();
+    let (sexpr, closing) = scan_list(scanner, errors, '{', '}', "s-expression")?;
+
+    let token = Token::new(TokenKind::SExpr(sexpr), scanner.span_from(sexpr_si));
+    return Some(with_closing_trivia(token, closing));
+}
+
+// (a + b * {mod a b}), the infix form is only read here and gets desugared in a later pass
+fn scan_sweet_expr(scanner: &mut Scanner, errors: &mut Vec<Error>) -> Option<Token> {
+    let sweet_si = scanner.get_source_info();
+    let (sweet, closing) = scan_list(scanner, errors, '(', ')', "sweet expression")?;
+
+    let token = Token::new(TokenKind::SweetExpr(sweet), scanner.span_from(sweet_si));
+    return Some(with_closing_trivia(token, closing));
+}
+
+fn with_closing_trivia(mut token: Token, closing: Option<Vec<Trivia>>) -> Token {
+    if let Some(closing) = closing {
+        token.trivia.get_or_insert_with(Box::default).closing = closing;
+    }
+
+    return token;
+}
+
+// Returns None if the list was never closed, the error for that is reported at the opening character.
+// The trivia before the closing character is returned in lossless mode.
+fn scan_list(
+    scanner: &mut Scanner,
+    errors: &mut Vec<Error>,
+    open: char,
+    close: char,
+    name: &str,
+) -> Option<(Vec<Token>, Option<Vec<Trivia>>)> {
+    let open_si = scanner.get_source_info();
+    scanner.advance();
+    let open_si = scanner.span_from(open_si);
+
+    let mut list = Vec::new();
+
+    loop {
+        skip_whitespace(scanner, errors);
+
+        if scanner.is_at_end() || is_top_level_start(scanner) {
+            errors.push(Error::new(
+                format!("Unclosed '{open}', expected '{close}' to end {name}"),
+                open_si,
+            ));
+
+            return None;
+        }
+
+        let c = scanner.peek();
+
+        if c == close {
+            // Doc comments at the end of a list don't document anything
+            scanner.take_doc();
+
+            let closing = scanner.trivia.is_some().then(|| scanner.take_trivia());
+            scanner.advance();
+            return Some((list, closing));
+        }
+
+        if is_closing_char(c) {
+            errors.push(unexpected_char(scanner));
+            scanner.advance();
+            synchronize(scanner);
+            continue;
+        }
+
+        if let Some(token) = scan_token(scanner, errors) {
+            list.push(token);
+        }
+    }
+}
+
+fn scan_string(scanner: &mut Scanner) -> Result<Token, Error> {
+    let si = scanner.get_source_info();
+
+    let is_bytes = scanner.match_char('b');
+    if is_bytes {
+        scanner.advance();
+    }
+
+    if scanner.match_char('r') {
+        return scan_raw_string(scanner, si, is_bytes);
+    }
+
+    scanner.advance();
+
+    let mut bytes = Vec::new();
+    let mut error = None;
+
+    loop {
+        if scanner.is_at_end() {
+            return Err(Error::new(
+                "Unterminated string literal".to_string(),
+                si,
+            ));
+        }
+
+        let c = scanner.peek();
+
+        let result = if c == '"' {
+            scanner.advance();
+            break;
+        } else if c == '\\' {
+            scan_escape(scanner, is_bytes).map(|escaped| {
+                if let Some(value) = escaped {
+                    push_string_char(&mut bytes, value, is_bytes);
+                }
+            })
+        } else if c.is_ascii() {
+            bytes.push(scanner.advance() as u8);
+            Ok(())
+        } else {
+            scan_utf8_char(scanner, &mut bytes, is_bytes)
+        };
+
+        // Keep going until the closing quote so the rest of the file is not misread
+        if let Err(e) = result {
+            error.get_or_insert(e);
+        }
+    }
+
+    if let Some(e) = error {
+        return Err(e);
+    }
+
+    return Ok(Token::new(string_kind(bytes, is_bytes), si));
+}
+
+// 'a', '\n', '\u{3bb}' and byte literals b'a'
+// A quote that is closed again before the end of the word it starts is a character, even one with
+// too many characters in it like 'ab'. Anything else after a quote is quoted code like 'a or '{1 2}
+fn is_char_literal(scanner: &Scanner) -> bool {
+    if !scanner.match_char('\'') {
+        return false;
+    }
+
+    let first = match scanner.text.get(scanner.index + 1) {
+        Some(b'\\') | Some(b'\'') | Some(b'\n') | None => return true,
+        Some(first) => *first,
+    };
+
+    let len = match first {
+        0xF0.. => 4,
+        0xE0.. => 3,
+        0xC0.. => 2,
+        _ => 1,
+    };
+
+    // ' ' and '{' are characters too
+    if scanner.text.get(scanner.index + 1 + len) == Some(&b'\'') {
+        return true;
+    }
+
+    for c in &scanner.text[scanner.index + 1..] {
+        match c {
+            b'\'' => return true,
+            b'{' | b'}' | b'(' | b')' | b'[' | b']' | b'"' | b'#' | b',' | b'`' => return false,
+            c if c.is_ascii_whitespace() => return false,
+            _ => {},
+        }
+    }
+
+    return false;
+}
+
+// 'x, `x, ,x and ,@x are read as {quote x}, {quasiquote x}, {unquote x} and {unquote-splicing x}
+fn scan_quote(scanner: &mut Scanner, errors: &mut Vec<Error>) -> Option<Token> {
+    let si = scanner.get_source_info();
+
+    let (prefix, name) = match scanner.peek() {
+        '\'' => ("'", Symbol::QUOTE),
+        '`' => ("`", Symbol::QUASIQUOTE),
+        _ if scanner.match_string(",@") => (",@", Symbol::UNQUOTE_SPLICING),
+        _ => (",", Symbol::UNQUOTE),
+    };
+
+    scanner.skip(prefix.len());
+    let head_si = scanner.span_from(si);
+
+    let c = scanner.peek();
+    if scanner.is_at_end() || c.is_whitespace() || c == '#' || is_closing_char(c) {
+        errors.push(Error::new(
+            format!("Expected an expression right after '{prefix}'"),
+            head_si,
+        ));
+        return None;
+    }
+
+    let quoted = scan_token(scanner, errors)?;
+    let head = with_trivia(scanner, Token::new(TokenKind::Identifier(name), head_si), Vec::new());
+
+    let mut token = Token::new(TokenKind::SExpr(vec![head, quoted]), scanner.span_from(si));
+    if scanner.trivia.is_some() {
+        token.trivia = Some(Box::new(TokenTrivia {
+            shorthand: true,
+            ..Default::default()
+        }));
+    }
+
+    return Some(token);
+}
+
+fn scan_char(scanner: &mut Scanner) -> Result<Token, Error> {
+    let si = scanner.get_source_info();
+
+    let is_byte = scanner.match_char('b');
+    if is_byte {
+        scanner.advance();
+    }
+
+    scanner.advance();
+
+    if scanner.is_at_end() || scanner.match_char('\n') {
+        return Err(Error::new(
+            "Unterminated character literal".to_string(),
+            si,
+        ));
+    }
+
+    if scanner.match_char('\'') {
+        scanner.advance();
+        return Err(Error::new(
+            "Empty character literal".to_string(),
+            scanner.span_from(si),
+        ));
+    }
+
+    let c = scanner.peek();
+    let value = if c == '\\' {
+        let escape_si = scanner.get_source_info();
+
+        match scan_escape(scanner, is_byte) {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => Err(Error::new(
+                "Line continuations are not allowed in character literals".to_string(),
+                escape_si,
+            )),
+            Err(e) => Err(e),
+        }
+    } else if c.is_ascii() {
+        Ok(scanner.advance())
+    } else {
+        let mut bytes = Vec::new();
+        scan_utf8_char(scanner, &mut bytes, is_byte)
+            .map(|_| std::str::from_utf8(&bytes).unwrap().chars().next().unwrap())
+    };
+
+    if value.is_ok() && !scanner.is_at_end() && scanner.match_char('\'') {
+        scanner.advance();
+
+        let kind = if is_byte {
+            TokenKind::Byte(value? as u8)
+        } else {
+            TokenKind::Char(value?)
+        };
+
+        return Ok(Token::new(kind, si));
+    }
+
+    // Find the closing quote on the same line to tell apart 'ab' and a missing quote
+    while !scanner.is_at_end() && !scanner.match_char('\'') && !scanner.match_char('\n') {
+        scanner.advance();
+    }
+
+    if let Err(e) = value {
+        if !scanner.is_at_end() && scanner.match_char('\'') {
+            scanner.advance();
+        }
+
+        return Err(e);
+    }
+
+    if scanner.is_at_end() || scanner.match_char('\n') {
+        return Err(Error::new(
+            "Unterminated character literal".to_string(),
+            si,
+        ));
+    }
+
+    scanner.advance();
+    return Err(Error::new(
+        "Character literals can only contain one character".to_string(),
+        scanner.span_from(si),
+    ));
+}
+
+// r"...", r#"..."#, br"...", the amount of '#' on both sides has to match
+fn scan_raw_string(scanner: &mut Scanner, si: SourceInfo, is_bytes: bool) -> Result<Token, Error> {
+    scanner.advance();
+
+    let mut n_hashes = 0;
+    while !scanner.is_at_end() && scanner.match_char('#') {
+        scanner.advance();
+        n_hashes += 1;
+    }
+
+    if scanner.is_at_end() || !scanner.match_char('"') {
+        return Err(Error::new(
+            "Expected '\"' to start a raw string literal".to_string(),
+            scanner.get_source_info(),
+        ));
+    }
+
+    scanner.advance();
+
+    let terminator = format!("\"{}", "#".repeat(n_hashes));
+    let mut bytes = Vec::new();
+    let mut error = None;
+
+    loop {
+        if scanner.is_at_end() {
+            return Err(Error::new(
+                "Unterminated raw string literal".to_string(),
+                si,
+            ));
+        }
+
+        if scanner.match_string(&terminator) {
+            scanner.skip(terminator.len());
+            break;
+        }
+
+        if scanner.peek().is_ascii() {
+            bytes.push(scanner.advance() as u8);
+        } else if let Err(e) = scan_utf8_char(scanner, &mut bytes, is_bytes) {
+            error.get_or_insert(e);
+        }
+    }
+
+    if let Some(e) = error {
+        return Err(e);
+    }
+
+    return Ok(Token::new(string_kind(bytes, is_bytes), si));
+}
+
+fn string_kind(bytes: Vec<u8>, is_bytes: bool) -> TokenKind {
+    if is_bytes {
+        TokenKind::ByteString(bytes)
+    } else {
+        // Every path that pushes into a non byte string checks for valid UTF-8
+        TokenKind::String(String::from_utf8(bytes).unwrap())
+    }
+}
+
+fn push_string_char(bytes: &mut Vec<u8>, value: char, is_bytes: bool) {
+    if is_bytes {
+        bytes.push(value as u8);
+    } else {
+        let mut buffer = [0; 4];
+        bytes.extend_from_slice(value.encode_utf8(&mut buffer).as_bytes());
+    }
+}
+
+// Scans an escape sequence starting at '\\'.
+// Returns None for a line continuation, which skips the newline and the indentation after it.
+// Byte literals get the raw byte value stored in the char.
+fn scan_escape(scanner: &mut Scanner, is_bytes: bool) -> Result<Option<char>, Error> {
+    let si = scanner.get_source_info();
+    scanner.advance();
+
+    let c = scanner.advance();
+    let value = match c {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        '0' => '\0',
+        '\\' | '"' | '\'' => c,
+
+        '\n' | '\r' => {
+            while !scanner.is_at_end() && scanner.peek().is_ascii_whitespace() {
+                scanner.advance();
+            }
+
+            return Ok(None);
+        },
+
+        'x' => {
+            let mut value = 0;
+
+            for _ in 0..2 {
+                let digit = match scanner.peek_digit(16) {
+                    Some(digit) => digit,
+                    None => return Err(Error::new(
+                        "Expected two hexadecimal digits in '\\x' escape".to_string(),
+                        scanner.get_source_info(),
+                    )),
+                };
+
+                value = value * 16 + digit;
+                scanner.advance();
+            }
+
+            if value > 0x7F && !is_bytes {
+                return Err(Error::new(
+                    "Only byte literals can use '\\x' escapes above \\x7F".to_string(),
+                    si,
+                ));
+            }
+
+            char::from(value as u8)
+        },
+
+        'u' => {
+            if is_bytes {
+                return Err(Error::new(
+                    "Unicode escapes are not allowed in byte literals".to_string(),
+                    si,
+                ));
+            }
+
+            scan_unicode_escape(scanner, si)?
+        },
+
+        '\0' if scanner.is_at_end() => return Err(Error::new(
+            "Unterminated escape sequence".to_string(),
+            si,
+        )),
+
+        _ => return Err(Error::new(
+            format!("Unknown escape sequence '\\{}'", display_char(c)),
+            si,
+        )),
+    };
+
+    return Ok(Some(value));
+}
+
+// \u{1F600}, between one and six hexadecimal digits that form a unicode scalar value
+fn scan_unicode_escape(scanner: &mut Scanner, si: SourceInfo) -> Result<char, Error> {
+    if scanner.is_at_end() || !scanner.match_char('{') {
+        return Err(Error::new(
+            "Expected '{' after '\\u'".to_string(),
+            scanner.get_source_info(),
+        ));
+    }
+
+    scanner.advance();
+
+    let mut value: u32 = 0;
+    let mut n_digits = 0;
+
+    while !scanner.is_at_end() && !scanner.match_char('}') {
+        let digit = match scanner.peek().to_digit(16) {
+            Some(digit) => digit,
+            None => return Err(Error::new(
+                format!("Invalid hexadecimal digit '{}' in unicode escape", display_char(scanner.peek())),
+                scanner.get_source_info(),
+            )),
+        };
+
+        if n_digits == 6 {
+            return Err(Error::new(
+                "Unicode escapes can have at most 6 hexadecimal digits".to_string(),
+                scanner.get_source_info(),
+            ));
+        }
+
+        value = value * 16 + digit;
+        n_digits += 1;
+        scanner.advance();
+    }
+
+    if scanner.is_at_end() {
+        return Err(Error::new(
+            "Expected '}' to end unicode escape".to_string(),
+            scanner.get_source_info(),
+        ));
+    }
+
+    if n_digits == 0 {
+        return Err(Error::new(
+            "Unicode escapes require at least one hexadecimal digit".to_string(),
+            scanner.get_source_info(),
+        ));
+    }
+
+    scanner.advance();
+
+    return char::from_u32(value).ok_or_else(|| Error::new(
+        format!("Invalid unicode scalar value {value:X} in unicode escape"),
+        si,
+    ));
+}
+
+// Copies one multi byte UTF-8 character from the source, only ASCII is allowed in byte literals
+fn scan_utf8_char(scanner: &mut Scanner, bytes: &mut Vec<u8>, is_bytes: bool) -> Result<(), Error> {
+    let si = scanner.get_source_info();
+    let start = scanner.index;
+    let c = scanner.advance();
+
+    if is_bytes {
+        return Err(Error::new(
+            "Byte literals can only contain ASCII characters, use a '\\x' escape instead".to_string(),
+            scanner.span_from(si),
+        ));
+    }
+
+    let source = &scanner.text[start..scanner.index];
+
+    // The scanner reads invalid UTF-8 as U+FFFD
+    if c == char::REPLACEMENT_CHARACTER && source != "\u{FFFD}".as_bytes() {
+        return Err(Error::new(
+            "Invalid UTF-8 in literal".to_string(),
+            scanner.span_from(si),
+        ));
+    }
+
+    bytes.extend_from_slice(source);
+
+    return Ok(());
+}
+
+fn scan_number(scanner: &mut Scanner) -> Result<Token, Error> {
+    let result = scan_number_literal(scanner);
+
+    // Skip the rest of a malformed literal so it isn't read as an identifier
+    if result.is_err() {
+        while !scanner.is_at_end() && (scanner.peek().is_ascii_alphanumeric() || scanner.match_char('_') || scanner.match_char('.')) {
+            scanner.advance();
+        }
+    }
 
-mod scanner;
-use scanner::Scanner;
+    return result;
+}
 
-// TODO: Clean this up
-// TODO: Maybe create a function that skips until the next S-Expression in certain cases
+fn scan_number_literal(scanner: &mut Scanner) -> Result<Token, Error> {
+    let si = scanner.get_source_info();
 
-// TODO: Might want to skip until the start of the next S-Expression on error.
-pub fn tokenize(ascii_text: &[u8]) -> Result<Vec<Token>, Vec<Error>> {
-    let mut scanner = Scanner::new(ascii_text);
-    let mut errors: Vec<Error> = Vec::new();
-    let mut tokens: Vec<Token> = Vec::new();
+    let radix = if scanner.match_string("0x") {
+        16
+    } else if scanner.match_string("0o") {
+        8
+    } else if scanner.match_string("0b") {
+        2
+    } else {
+        10
+    };
 
-    skip_whitespace(&mut scanner);
-    while !scanner.is_at_end() {
-        match scan_token(&mut scanner) {
-            Ok(token) => match &token.kind {
-                TokenKind::TypeExpr(_) => errors.push(Error {
-                    message: "Type expressions can not be written at the top level".to_string(),
-                    si: token.si,
-                }),
-
-                _ => tokens.push(token),
-            },
+    if radix != 10 {
+        scanner.skip(2);
+    }
 
-            Err(error) => errors.push(error),
+    let integer = scan_digits(scanner, radix)?;
+    let mut is_float = false;
+
+    if radix == 10 && scanner.match_char('.') {
+        is_float = true;
+        scanner.advance();
+
+        if !scanner.peek().is_ascii_digit() {
+            return Err(Error::new(
+                "Expected at least one digit after '.'".to_string(),
+                scanner.get_source_info(),
+            ));
         }
 
-        skip_whitespace(&mut scanner);
+        scan_digits(scanner, radix)?;
+
+        if scanner.match_char('.') {
+            return Err(Error::new(
+                "Duplicate decimal point in float".to_string(),
+                scanner.get_source_info(),
+            ));
+        }
     }
 
-    return if errors.len() > 0 { Err(errors) } else { Ok(tokens) };
-}
+    if radix == 10 && (scanner.match_char('e') || scanner.match_char('E')) {
+        is_float = true;
+        scanner.advance();
 
-fn scan_token(scanner: &mut Scanner) -> Result<Token, Error> {
-    let c = scanner.peek();
+        if scanner.match_char('+') || scanner.match_char('-') {
+            scanner.advance();
+        }
 
-    match c {
-        '{' => return scan_sexpr(scanner),
+        if !scanner.peek().is_ascii_digit() {
+            return Err(Error::new(
+                "Expected at least one digit in exponent".to_string(),
+                scanner.get_source_info(),
+            ));
+        }
 
-        'a'..='z'|'A'..='Z'|
-        '!'|'$'..='&'|'*'|'+'|
-        '-'|'/'|':'..='@'|'\\'|
-        '^'..='`'|'|'|'~' => return scan_identifier(scanner),
+        scan_digits(scanner, radix)?;
+    }
 
-        '0'..='9' => return scan_number(scanner),
+    let literal_end = scanner.index;
+    let suffix = scan_number_suffix(scanner)?;
 
-        '"' => return scan_string(scanner),
-        
-        _ => todo!(),
+    if is_float || matches!(suffix, Some(NumberSuffix::Float(_))) {
+        let float_type = match suffix {
+            Some(NumberSuffix::Int(int_type)) => return Err(Error::new(
+                format!("Float literal can not have the integer suffix '{int_type}'"),
+                scanner.span_from(si),
+            )),
+
+            Some(NumberSuffix::Float(float_type)) => Some(float_type),
+            None => None,
+        };
+
+        let slice = &scanner.text[si.index..literal_end];
+        let s: String = std::str::from_utf8(slice).unwrap().chars().filter(|&c| c != '_').collect();
+        let value = f64::from_str(&s).unwrap();
+
+        let in_range = match float_type {
+            Some(FloatType::F32) => (value as f32).is_finite(),
+            _ => value.is_finite(),
+        };
+
+        if !in_range {
+            return Err(Error::new(
+                format!("Float literal out of range for {}", float_type.unwrap_or(FloatType::F64)),
+                scanner.span_from(si),
+            ));
+        }
+
+        return Ok(Token::new(TokenKind::Float(value, float_type), si));
     }
-}
 
-fn scan_sexpr(scanner: &mut Scanner) -> Result<Token, Error> {
-    let sexpr_si = scanner.get_source_info();
+    let int_type = match suffix {
+        Some(NumberSuffix::Int(int_type)) => Some(int_type),
+        _ => None,
+    };
 
-    let c = scanner.advance();
-    if c != '{' {
+    let magnitude = match integer.overflow {
+        Some(error) => return Err(error),
+        None => integer.value,
+    };
+
+    if int_type.is_some_and(|t| magnitude > t.max()) {
         return Err(Error::new(
-            "Expected '{' to start an s-expression".to_string(),
-            scanner.get_source_info(),
+            format!("Integer literal out of range for {}", int_type.unwrap()),
+            scanner.span_from(si),
         ));
     }
 
-    let mut sexpr = Vec::new();
+    return Ok(Token::new(TokenKind::UInt(magnitude, int_type), si));
+}
+
+enum NumberSuffix {
+    Int(IntType),
+    Float(FloatType),
+}
 
-    skip_whitespace(scanner);
-    while !scanner.is_at_end() && !scanner.match_char('}') {
-        let token = scan_token(scanner)?;
-        sexpr.push(token);
-        skip_whitespace(scanner);
+fn scan_number_suffix(scanner: &mut Scanner) -> Result<Option<NumberSuffix>, Error> {
+    let si = scanner.get_source_info();
+
+    while !scanner.is_at_end() && scanner.peek().is_ascii_alphanumeric() {
+        scanner.advance();
     }
 
-    let c = scanner.advance();
-    if c != '}' {
+    let suffix = &scanner.text[si.index..scanner.index];
+    let suffix = match suffix {
+        b"" => return Ok(None),
+
+        b"i8" => NumberSuffix::Int(IntType::I8),
+        b"i16" => NumberSuffix::Int(IntType::I16),
+        b"i32" => NumberSuffix::Int(IntType::I32),
+        b"i64" => NumberSuffix::Int(IntType::I64),
+        b"i128" => NumberSuffix::Int(IntType::I128),
+        b"u8" => NumberSuffix::Int(IntType::U8),
+        b"u16" => NumberSuffix::Int(IntType::U16),
+        b"u32" => NumberSuffix::Int(IntType::U32),
+        b"u64" => NumberSuffix::Int(IntType::U64),
+        b"u128" => NumberSuffix::Int(IntType::U128),
+
+        b"f32" => NumberSuffix::Float(FloatType::F32),
+        b"f64" => NumberSuffix::Float(FloatType::F64),
+
+        _ => return Err(Error::new(
+            format!("Invalid number literal suffix '{}'", String::from_utf8_lossy(suffix)),
+            si,
+        )),
+    };
+
+    return Ok(Some(suffix));
+}
+
+struct Digits {
+    value: u128,
+
+    // Overflow only matters for integer literals so it is reported by the caller
+    overflow: Option<Error>,
+}
+
+// Scans a run of digits in the given radix, skipping '_' separators.
+// Errors point at the digit that caused them.
+fn scan_digits(scanner: &mut Scanner, radix: u32) -> Result<Digits, Error> {
+    let mut value: u128 = 0;
+    let mut overflow = None;
+    let mut n_digits = 0;
+
+    while !scanner.is_at_end() {
+        let c = scanner.peek();
+
+        if c == '_' {
+            scanner.advance();
+            continue;
+        }
+
+        if !c.is_ascii_alphanumeric() || is_suffix_start(c, radix) {
+            break;
+        }
+
+        let digit = match c.to_digit(radix) {
+            Some(digit) => digit,
+            None => return Err(Error::new(
+                format!("Invalid digit '{c}' in {} literal", radix_name(radix)),
+                scanner.get_source_info(),
+            )),
+        };
+
+        match value.checked_mul(radix as u128).and_then(|v| v.checked_add(digit as u128)) {
+            Some(v) => value = v,
+            None => if overflow.is_none() {
+                overflow = Some(Error::new(
+                    "Integer literal exceeds maximum integer size".to_string(),
+                    scanner.get_source_info(),
+                ));
+            },
+        }
+
+        n_digits += 1;
+        scanner.advance();
+    }
+
+    if n_digits == 0 {
         return Err(Error::new(
-            "Expected '}' to end s-expression".to_string(),
+            format!("Expected at least one digit in {} literal", radix_name(radix)),
             scanner.get_source_info(),
         ));
     }
 
-    return Ok(Token {
-        kind: TokenKind::SExpr(sexpr),
-        si: sexpr_si,
-    });
+    return Ok(Digits { value, overflow });
 }
 
-fn scan_string(scanner: &mut Scanner) -> Result<Token, Error> {
-    let si = scanner.get_source_info();
-    
-    scanner.advance();
-    let start = scanner.index;
+// Characters that end a digit run and start an exponent or a type suffix
+fn is_suffix_start(c: char, radix: u32) -> bool {
+    match c {
+        'i' | 'u' => true,
+        'e' | 'E' | 'f' => radix == 10,
+        _ => false,
+    }
+}
 
-    while !scanner.is_at_end() {
-        let c = scanner.advance();
-        if c == '"' {
-            let slice = &scanner.text[start..scanner.index - 1];
-            return Ok(Token {
-                kind: TokenKind::String(slice.to_vec()),
-                si,
-            });
+fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        16 => "hexadecimal",
+        _ => "decimal",
+    }
+}
+
+// Identifiers with dots in them like Std.Console are read as paths
+fn scan_identifier(scanner: &mut Scanner) -> Result<Token, Error> {
+    let result = scan_path(scanner);
+
+    if result.is_err() {
+        while !scanner.is_at_end() && (valid_identifier_char(scanner.peek()) || scanner.match_char('.')) {
+            scanner.advance();
         }
     }
 
-    return Err(Error {
-        message: "Unterminated string literal".to_string(),
-        si: scanner.get_source_info(),
-    });
+    return result;
 }
 
-fn scan_number(scanner: &mut Scanner) -> Result<Token, Error> {
+fn scan_path(scanner: &mut Scanner) -> Result<Token, Error> {
     let si = scanner.get_source_info();
-    let mut integer_base = 10;
 
-    if scanner.match_string("0x") {
-        integer_base = 16;
-        scanner.skip(2);
-        todo!()
-    } else if scanner.match_string("0o") {
-        integer_base = 8;
-        scanner.skip(2);
-        todo!()
-    } else if scanner.match_string("0b") {
-        integer_base = 2;
-        scanner.skip(2);
-        todo!()
-    } else {
-        let mut is_float = false;
-        let mut valid_float = false;
-        let start = scanner.index;
-
-        while !scanner.is_at_end() {
-            let c = scanner.peek();
-            
-            if is_float && !valid_float {
-                if c.is_ascii_digit() {
-                    valid_float = true;
-                } else {
-                    return Err(Error::new(
-                        "Expected at least one digit after '.'".to_string(),
-                        scanner.get_source_info(),
-                    ));
-                }
-            }
+    let mut segments = Vec::new();
+    let mut segment_si = si;
 
-            if c == '.' {
-                if !is_float {
-                    is_float = true;
-                } else {
-                    return Err(Error::new(
-                        "Duplicate decimal point in float".to_string(),
-                        scanner.get_source_info(),
-                    ));
-                }
+    loop {
+        while !scanner.is_at_end() && valid_identifier_char(scanner.peek()) {
+            scanner.advance();
+        }
+
+        let text = scanner.text;
+        let slice = &text[segment_si.index..scanner.index];
+        let name = scanner.interner.intern(&String::from_utf8_lossy(slice));
+
+        // rest.. is a variadic parameter
+        if scanner.match_string("..") {
+            scanner.skip(2);
+
+            if !segments.is_empty() {
+                return Err(Error::new(
+                    "Qualified names can not be variadic".to_string(),
+                    scanner.span_from(si),
+                ));
             }
 
-            if !c.is_ascii_digit() && c != '.' {
-                break;
+            if !scanner.is_at_end() && (valid_identifier_char(scanner.peek()) || scanner.match_char('.')) {
+                return Err(Error::new(
+                    "Expected the variadic name to end after '..'".to_string(),
+                    scanner.get_source_info(),
+                ));
             }
 
-            scanner.advance();
+            return Ok(Token::new(TokenKind::Variadic(name), si));
         }
 
-        let slice = &scanner.text[start..scanner.index];
-        let s = std::str::from_utf8(slice).unwrap();
+        if !scanner.match_char('.') {
+            segments.push(PathSegment { name, si: scanner.span_from(segment_si) });
+            break;
+        }
 
-        if is_float {
-            let value = f64::from_str(s).unwrap();
-            return Ok(Token {
-                kind: TokenKind::Float(value),
-                si,
-            });
+        if slice.is_empty() {
+            return Err(Error::new(
+                "Expected a name before '.'".to_string(),
+                scanner.get_source_info(),
+            ));
         }
 
-        let value = match u128::from_str_radix(s, integer_base) {
-            Ok(v) => v,
-            Err(e) => {
-                let message = match e.kind() {
-                    IntErrorKind::PosOverflow => "Integer literal exeeds maximum integer size",
-                    _ => unreachable!(),
-                };
+        segments.push(PathSegment { name, si: scanner.span_from(segment_si) });
+        scanner.advance();
 
-                return Err(Error::new(
-                    message.to_string(),
-                    si,
-                )); 
-            },
-        };
+        if scanner.is_at_end() || !valid_identifier_char(scanner.peek()) {
+            return Err(Error::new(
+                "Expected a name after '.'".to_string(),
+                scanner.get_source_info(),
+            ));
+        }
 
-        return Ok(Token {
-            kind: TokenKind::UInt(value),
-            si,
-        });
+        segment_si = scanner.get_source_info();
+    }
+
+    if segments.len() == 1 {
+        let name = segments.pop().unwrap().name;
+        return Ok(Token::new(TokenKind::Identifier(name), si));
     }
+
+    return Ok(Token::new(TokenKind::Path(segments), si));
 }
 
-fn scan_identifier(scanner: &mut Scanner) -> Result<Token, Error> {
+// @name, labels are identifiers with an '@' in front of them
+fn scan_label(scanner: &mut Scanner) -> Result<Token, Error> {
     let si = scanner.get_source_info();
+    scanner.advance();
 
-    while !scanner.is_at_end() {
-        let c = scanner.peek();
-        if !valid_identifier_char(c) {
-            break;
-        }
-
-        scanner.advance();
+    if scanner.is_at_end() || !valid_identifier_char(scanner.peek()) || scanner.match_char('@') {
+        return Err(Error::new(
+            "Expected a label name after '@'".to_string(),
+            scanner.span_from(si),
+        ));
     }
 
-    let slice = &scanner.text[si.index..scanner.index];
-    let id = String::from_utf8_lossy(slice);
+    let name = match scan_identifier(scanner)?.kind {
+        TokenKind::Identifier(name) => name,
+        _ => return Err(Error::new(
+            "Labels can not be qualified names".to_string(),
+            scanner.span_from(si),
+        )),
+    };
 
-    return Ok(Token {
-        kind: TokenKind::Identifier(id.to_string()),
-        si,
-    });
+    return Ok(Token::new(TokenKind::Label(name), si));
 }
 
+// ASCII symbols and letters plus any other Unicode identifier character
 fn valid_identifier_char(c: char) -> bool {
-    c.is_ascii() && (
+    is_xid_continue(c) || c.is_ascii() && (
     c == '!' ||
     (c >= '$' && c <= '&') ||
     c == '*' ||
@@ -233,37 +1075,383 @@ fn valid_identifier_char(c: char) -> bool {
     c == '-' ||
     (c >= '/' && c <= 'Z') ||
     c == '\\' ||
-    (c >= '^' && c <= 'z') ||
+    c == '^' ||
+    c == '_' ||
+    (c >= 'a' && c <= 'z') ||
     c == '|' ||
     c == '~')
 }
 
-// Skips whitespace and comments
-fn skip_whitespace(scanner: &mut Scanner) {
+// Skips whitespace and comments, they are kept as trivia in lossless mode
+fn skip_whitespace(scanner: &mut Scanner, errors: &mut Vec<Error>) {
     while !scanner.is_at_end() {
+        let si = scanner.get_source_info();
         let c = scanner.peek();
         
-        if c.is_whitespace() {
-            scanner.advance();
+        let kind = if c.is_whitespace() {
+            while !scanner.is_at_end() && scanner.peek().is_whitespace() {
+                scanner.advance();
+            }
+
+            TriviaKind::Whitespace
+        } else if scanner.match_string("#-|") {
+            scanner.skip(3);
+            let start = scanner.index;
+
+            while !scanner.is_at_end() && !scanner.match_string("-#") {
+                scanner.advance();
+            }
+
+            if scanner.is_at_end() {
+                errors.push(Error::new(
+                    "Unterminated doc comment".to_string(),
+                    scanner.span_from(si),
+                ));
+            }
+
+            let doc = block_doc_text(&scanner.text[start..scanner.index]);
+            scanner.docs.push(doc);
+
+            scanner.skip(2);
+            TriviaKind::DocComment
+        } else if scanner.match_string("#|") {
+            scanner.skip(2);
+            let start = scanner.index;
+
+            while !scanner.is_at_end() && !scanner.match_char('\n') {
+                scanner.advance();
+            }
+
+            let line = String::from_utf8_lossy(&scanner.text[start..scanner.index]);
+            let line = line.strip_prefix(' ').unwrap_or(&line).trim_end();
+            scanner.docs.push(line.to_string());
+
+            TriviaKind::DocComment
+        } else if scanner.match_string("#-") {
+            scanner.skip(2);
+
+            while !scanner.is_at_end() && !scanner.match_string("-#") {
+                scanner.advance();
+            }
+
+            if scanner.is_at_end() {
+                errors.push(Error::new(
+                    "Unterminated block comment".to_string(),
+                    scanner.span_from(si),
+                ));
+            }
+
+            scanner.skip(2);
+            TriviaKind::BlockComment
         } else if c == '#' {
-            scanner.advance();
-            if scanner.advance() == '-' {
-                while !scanner.is_at_end() {
-                    if scanner.advance() == '-' {
-                        if scanner.advance() == '#' {
-                            break;
-                        }
-                    }
-                }
-            } else {
-                while !scanner.is_at_end() {
-                    if scanner.advance() == '\n' {
-                        break;
-                    }
-                }
+            // The newline is whitespace and not part of the comment
+            while !scanner.is_at_end() && !scanner.match_char('\n') {
+                scanner.advance();
             }
+
+            TriviaKind::LineComment
         } else {
             break;
+        };
+
+        let si = scanner.span_from(si);
+        let text = scanner.text;
+
+        if let Some(trivia) = &mut scanner.trivia {
+            trivia.push(Trivia {
+                kind,
+                text: text[si.index..si.end_index].to_vec(),
+                si,
+            });
+        }
+    }
+}
+
+// Removes the blank lines around a block doc comment and the indentation all of its lines share
+fn block_doc_text(text: &[u8]) -> String {
+    let text = String::from_utf8_lossy(text);
+    let lines: Vec<&str> = text.lines().map(|l| l.trim_end()).collect();
+
+    let first = lines.iter().position(|l| !l.is_empty()).unwrap_or(lines.len());
+    let last = lines.iter().rposition(|l| !l.is_empty()).map_or(first, |i| i + 1);
+    let lines = &lines[first..last];
+
+    let indentation = lines.iter()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    return lines.iter()
+        .map(|l| l.get(indentation..).unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<TokenKind> {
+        let mut interner = Interner::new();
+        let tokens = tokenize(text.as_bytes(), FileId(0), &mut interner).unwrap();
+
+        return tokens.into_iter().map(|t| t.kind).collect();
+    }
+
+    // Line, column and message of every error
+    fn errors(text: &str) -> Vec<(i64, i64, String)> {
+        let mut interner = Interner::new();
+        let errors = tokenize(text.as_bytes(), FileId(0), &mut interner).unwrap_err();
+
+        return errors.into_iter().map(|e| (e.si.line, e.si.column, e.message)).collect();
+    }
+
+    fn error(line: i64, column: i64, message: &str) -> (i64, i64, String) {
+        (line, column, message.to_string())
+    }
+
+    #[test]
+    fn exponents() {
+        let kinds = kinds("6.02e23 1e-9 2E+3 1_0e1_0");
+
+        assert!(matches!(kinds[0], TokenKind::Float(v, None) if v == 6.02e23));
+        assert!(matches!(kinds[1], TokenKind::Float(v, None) if v == 1e-9));
+        assert!(matches!(kinds[2], TokenKind::Float(v, None) if v == 2e3));
+        assert!(matches!(kinds[3], TokenKind::Float(v, None) if v == 10e10));
+    }
+
+    #[test]
+    fn number_suffixes() {
+        let kinds = kinds("42i32 7u8 255u8 1.5f32 3f64 0xFFu16 1e2f32");
+
+        assert!(matches!(kinds[0], TokenKind::UInt(42, Some(IntType::I32))));
+        assert!(matches!(kinds[1], TokenKind::UInt(7, Some(IntType::U8))));
+        assert!(matches!(kinds[2], TokenKind::UInt(255, Some(IntType::U8))));
+        assert!(matches!(kinds[3], TokenKind::Float(v, Some(FloatType::F32)) if v == 1.5));
+        assert!(matches!(kinds[4], TokenKind::Float(v, Some(FloatType::F64)) if v == 3.0));
+        assert!(matches!(kinds[5], TokenKind::UInt(0xFF, Some(IntType::U16))));
+        assert!(matches!(kinds[6], TokenKind::Float(v, Some(FloatType::F32)) if v == 100.0));
+    }
+
+    #[test]
+    fn number_suffix_errors() {
+        assert_eq!(errors("256u8"), [error(1, 1, "Integer literal out of range for u8")]);
+        assert_eq!(errors("1.5i32"), [error(1, 1, "Float literal can not have the integer suffix 'i32'")]);
+        assert_eq!(errors("1e39f32"), [error(1, 1, "Float literal out of range for f32")]);
+        assert_eq!(errors("1i7"), [error(1, 2, "Invalid number literal suffix 'i7'")]);
+        assert_eq!(errors("1e"), [error(1, 3, "Expected at least one digit in exponent")]);
+        assert_eq!(errors("1e+x"), [error(1, 4, "Expected at least one digit in exponent")]);
+        assert_eq!(errors("12q"), [error(1, 3, "Invalid digit 'q' in decimal literal")]);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let kinds = kinds(r#""a\n\t\"\\\0\x41\u{3bb}\u{1F600}" "one \
+                                two" b"\xFF\n""#);
+
+        assert!(matches!(&kinds[0], TokenKind::String(s) if s == "a\n\t\"\\\0A\u{3bb}\u{1F600}"));
+        assert!(matches!(&kinds[1], TokenKind::String(s) if s == "one two"));
+        assert!(matches!(&kinds[2], TokenKind::ByteString(b) if b == b"\xFF\n"));
+    }
+
+    #[test]
+    fn raw_strings() {
+        let kinds = kinds("r#\"say \"hi\" \\n\"# r\"a\nb\" br##\"\\x\"#\"##");
+
+        assert!(matches!(&kinds[0], TokenKind::String(s) if s == "say \"hi\" \\n"));
+        assert!(matches!(&kinds[1], TokenKind::String(s) if s == "a\nb"));
+        assert!(matches!(&kinds[2], TokenKind::ByteString(b) if b == b"\\x\"#"));
+    }
+
+    #[test]
+    fn string_errors() {
+        let cases = [
+            (r#""\q""#, 2, "Unknown escape sequence '\\q'"),
+            (r#""\x4""#, 5, "Expected two hexadecimal digits in '\\x' escape"),
+            (r#""\xFF""#, 2, "Only byte literals can use '\\x' escapes above \\x7F"),
+            (r#""\u41""#, 4, "Expected '{' after '\\u'"),
+            (r#""\u{}""#, 5, "Unicode escapes require at least one hexadecimal digit"),
+            (r#""\u{1234567}""#, 11, "Unicode escapes can have at most 6 hexadecimal digits"),
+            (r#""\u{D800}""#, 2, "Invalid unicode scalar value D800 in unicode escape"),
+            (r#"b"\u{41}""#, 3, "Unicode escapes are not allowed in byte literals"),
+            ("b\"é\"", 3, "Byte literals can only contain ASCII characters, use a '\\x' escape instead"),
+            ("\"abc", 1, "Unterminated string literal"),
+            ("r#\"abc\"", 1, "Unterminated raw string literal"),
+            ("r#abc", 3, "Expected '\"' to start a raw string literal"),
+
+            // Only the first error in a string is reported
+            (r#""\q \w" 1"#, 2, "Unknown escape sequence '\\q'"),
+        ];
+
+        for (text, column, message) in cases {
+            assert_eq!(errors(text), [error(1, column, message)], "{text}");
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_in_string() {
+        let mut interner = Interner::new();
+        let errors = tokenize(b"\"a\xFFb\"", FileId(0), &mut interner).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].si.column, errors[0].message.as_str()), (3, "Invalid UTF-8 in literal"));
+    }
+
+    #[test]
+    fn char_literals() {
+        let kinds = kinds(r"'a' '\n' '\'' '\u{3bb}' 'é' ' ' '{' b'z' b'\xFF' b'\''");
+
+        let chars: Vec<char> = kinds[..7].iter().map(|k| match k {
+            TokenKind::Char(c) => *c,
+            k => panic!("expected a character, got {k:?}"),
+        }).collect();
+
+        assert_eq!(chars, ['a', '\n', '\'', '\u{3bb}', 'é', ' ', '{']);
+
+        assert!(matches!(kinds[7], TokenKind::Byte(b'z')));
+        assert!(matches!(kinds[8], TokenKind::Byte(0xFF)));
+        assert!(matches!(kinds[9], TokenKind::Byte(b'\'')));
+    }
+
+    // A quote that isn't closed before the end of the word quotes the code after it
+    #[test]
+    fn quote_is_not_a_char() {
+        let kinds = kinds("'a '{1 2} 'a'");
+
+        assert!(matches!(kinds[0], TokenKind::SExpr(_)));
+        assert!(matches!(kinds[1], TokenKind::SExpr(_)));
+        assert!(matches!(kinds[2], TokenKind::Char('a')));
+    }
+
+    #[test]
+    fn char_errors() {
+        let cases = [
+            ("''", 1, "Empty character literal"),
+            ("'ab'", 1, "Character literals can only contain one character"),
+            ("'", 1, "Unterminated character literal"),
+            ("'\nb", 1, "Unterminated character literal"),
+            (r"'\q'", 2, "Unknown escape sequence '\\q'"),
+            (r"b'\u{41}'", 3, "Unicode escapes are not allowed in byte literals"),
+            ("b'é'", 3, "Byte literals can only contain ASCII characters, use a '\\x' escape instead"),
+            ("'\\\n  a'", 2, "Line continuations are not allowed in character literals"),
+        ];
+
+        for (text, column, message) in cases {
+            assert_eq!(errors(text), [error(1, column, message)], "{text}");
         }
     }
+
+    fn docs(text: &str) -> Vec<Option<String>> {
+        let mut interner = Interner::new();
+        let tokens = tokenize(text.as_bytes(), FileId(0), &mut interner).unwrap();
+
+        return tokens.into_iter().map(|t| t.doc).collect();
+    }
+
+    #[test]
+    fn doc_comments() {
+        let text = "
+#| Adds two numbers
+#|   keeping the indentation after the first space
+{function add {x y} {+ x y}}
+
+# Plain comments are not docs
+{let x 1}
+
+#-|
+    Block docs lose the indentation
+      their lines share
+-#
+{let y 2}
+
+#| Other comments in between are skipped
+#- block -#
+# line
+{let z 3}
+";
+
+        assert_eq!(docs(text), [
+            Some("Adds two numbers\n  keeping the indentation after the first space".to_string()),
+            None,
+            Some("Block docs lose the indentation\n  their lines share".to_string()),
+            Some("Other comments in between are skipped".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn doc_comment_errors() {
+        assert_eq!(errors("{let x 1}\n#-| never closed"), [error(2, 1, "Unterminated doc comment")]);
+        assert_eq!(errors("#- never closed"), [error(1, 1, "Unterminated block comment")]);
+    }
+
+    #[test]
+    fn labels() {
+        let mut interner = Interner::new();
+        let tokens = tokenize(b"@name @is-valid? @\xCE\xBB", FileId(0), &mut interner).unwrap();
+
+        let names: Vec<&str> = tokens.iter().map(|t| match t.kind {
+            TokenKind::Label(name) => interner.resolve(name),
+            ref k => panic!("expected a label, got {k:?}"),
+        }).collect();
+
+        assert_eq!(names, ["name", "is-valid?", "\u{3bb}"]);
+    }
+
+    #[test]
+    fn label_errors() {
+        assert_eq!(errors("@"), [error(1, 1, "Expected a label name after '@'")]);
+        assert_eq!(errors("{f @ 1}"), [error(1, 4, "Expected a label name after '@'")]);
+        assert_eq!(errors("@@name"), [error(1, 1, "Expected a label name after '@'")]);
+        assert_eq!(errors("@Gender.Female"), [error(1, 1, "Labels can not be qualified names")]);
+    }
+
+    #[test]
+    fn paths() {
+        let mut interner = Interner::new();
+        let tokens = tokenize(b"Std.Console Gender.Female.x name", FileId(0), &mut interner).unwrap();
+
+        let segments = |token: &Token| match &token.kind {
+            TokenKind::Path(segments) => segments.iter()
+                .map(|s| (interner.resolve(s.name).to_string(), s.si.column, s.si.end_column))
+                .collect::<Vec<_>>(),
+            k => panic!("expected a path, got {k:?}"),
+        };
+
+        assert_eq!(segments(&tokens[0]), [("Std".to_string(), 1, 4), ("Console".to_string(), 5, 12)]);
+        assert_eq!(segments(&tokens[1]).len(), 3);
+        assert!(matches!(tokens[2].kind, TokenKind::Identifier(_)));
+    }
+
+    #[test]
+    fn path_errors() {
+        assert_eq!(errors("Std."), [error(1, 5, "Expected a name after '.'")]);
+        assert_eq!(errors("{Std. 1}"), [error(1, 6, "Expected a name after '.'")]);
+        assert_eq!(errors("Std.rest.."), [error(1, 1, "Qualified names can not be variadic")]);
+        assert_eq!(errors("rest..x"), [error(1, 7, "Expected the variadic name to end after '..'")]);
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        let mut interner = Interner::new();
+        let tokens = tokenize("{λ 名前 \"😀\" x_ü}".as_bytes(), FileId(0), &mut interner).unwrap();
+        let list = tokens[0].sexpr().unwrap();
+
+        let names: Vec<&str> = [&list[0], &list[1], &list[3]].iter()
+            .map(|t| interner.resolve(t.identifier().unwrap()))
+            .collect();
+
+        assert_eq!(names, ["λ", "名前", "x_ü"]);
+
+        // Columns count characters, not bytes
+        let columns: Vec<(i64, i64)> = list.iter().map(|t| (t.si.column, t.si.end_column)).collect();
+        assert_eq!(columns, [(2, 3), (4, 6), (7, 10), (11, 14)]);
+    }
+
+    #[test]
+    fn unicode_error_positions() {
+        assert_eq!(errors("\"😀\" €"), [error(1, 5, "Unexpected character '€'")]);
+        assert_eq!(errors("{名前 \"Ωμέγα\\q\"}"), [error(1, 11, "Unknown escape sequence '\\q'")]);
+        assert_eq!(errors("\"日本\"\n  ü €"), [error(2, 5, "Unexpected character '€'")]);
+    }
 }
@@ -1,28 +1,53 @@
-use crate::utils::SourceInfo;
+use crate::{
+    utils::{FileId, SourceInfo, Interner},
+    token::Trivia,
+};
 
 pub struct Scanner<'a> {
     pub text: &'a [u8],
     pub index: usize,
 
+    file: FileId,
+
     line: i64,
+
+    // Counted in characters, not bytes
     column: i64,
 
-    newline: bool,
+    // Trivia that was skipped since the last token, only collected in lossless mode
+    pub trivia: Option<Vec<Trivia>>,
+
+    // Doc comments since the last token
+    pub docs: Vec<String>,
+
+    pub interner: &'a mut Interner,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(text: &'a [u8]) -> Self {
+    pub fn new(text: &'a [u8], file: FileId, interner: &'a mut Interner) -> Self {
         Self {
             text,
             index: 0,
 
+            file,
+
             line: 1,
             column: 1,
 
-            newline: false,
+            trivia: None,
+            docs: Vec::new(),
+
+            interner,
         }
     }
 
+    // Continues from a position an earlier scan of the same text has reached
+    pub fn seek(&mut self, index: usize, line: i64, column: i64) {
+        self.index = index;
+        self.line = line;
+        self.column = column;
+    }
+
     pub fn is_at_end(&self) -> bool {
         self.index >= self.text.len()
     }
@@ -32,25 +57,21 @@ impl<'a> Scanner<'a> {
             return '\0';
         }
 
-        let c = self.text[self.index] as char;
+        let (c, len) = self.decode(self.index);
 
-        if self.newline {
-            self.newline = false;
+        if c == '\n' {
             self.line += 1;
             self.column = 1;
         } else {
             self.column += 1;
         }
 
-        if c == '\n' {
-            self.newline = true;
-        }
-
-        self.index += 1;
+        self.index += len;
 
         return c;
     }
 
+    // Skips amount characters
     pub fn skip(&mut self, amount: usize) {
         for _ in 0..amount {
             if self.advance() == '\0' {
@@ -59,8 +80,52 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    // Returns '\0' at the end of the text, check is_at_end if the text can contain '\0'
     pub fn peek(&self) -> char {
-        self.text[self.index] as char
+        if self.is_at_end() {
+            return '\0';
+        }
+
+        self.decode(self.index).0
+    }
+
+    pub fn peek_next(&self) -> char {
+        if self.is_at_end() {
+            return '\0';
+        }
+
+        let next = self.index + self.decode(self.index).1;
+        if next >= self.text.len() {
+            return '\0';
+        }
+
+        self.decode(next).0
+    }
+
+    // The character at index and its length in bytes, invalid UTF-8 is read one byte at a time
+    // as U+FFFD so the scanner can always make progress.
+    fn decode(&self, index: usize) -> (char, usize) {
+        let b = self.text[index];
+        if b < 0x80 {
+            return (b as char, 1);
+        }
+
+        let len = match b {
+            0xF0.. => 4,
+            0xE0.. => 3,
+            0xC0.. => 2,
+            _ => return (char::REPLACEMENT_CHARACTER, 1),
+        };
+
+        let end = (index + len).min(self.text.len());
+        match std::str::from_utf8(&self.text[index..end]) {
+            Ok(s) => (s.chars().next().unwrap(), len),
+            Err(_) => (char::REPLACEMENT_CHARACTER, 1),
+        }
+    }
+
+    pub fn peek_digit(&self, radix: u32) -> Option<u32> {
+        self.peek().to_digit(radix)
     }
 
     pub fn match_string(&self, s: &str) -> bool {
@@ -76,7 +141,26 @@ impl<'a> Scanner<'a> {
         self.peek() == c
     }
 
+    pub fn take_trivia(&mut self) -> Vec<Trivia> {
+        self.trivia.as_mut().map_or(Vec::new(), std::mem::take)
+    }
+
+    pub fn take_doc(&mut self) -> Option<String> {
+        if self.docs.is_empty() {
+            return None;
+        }
+
+        let doc = self.docs.join("\n");
+        self.docs.clear();
+        return Some(doc);
+    }
+
     pub fn get_source_info(&self) -> SourceInfo {
-        SourceInfo::new(self.line, self.column, self.index)
+        SourceInfo::new(self.file, self.line, self.column, self.index)
+    }
+
+    // Span from start up to the current position
+    pub fn span_from(&self, start: SourceInfo) -> SourceInfo {
+        start.to(self.get_source_info())
     }
 }
@@ -0,0 +1,552 @@
+use crate::{
+    utils::*,
+    token::*,
+};
+
+use unicode_ident::{is_xid_start, is_xid_continue};
+
+use super::{
+    scanner::Scanner,
+    display_char,
+    skip_whitespace,
+    synchronize,
+};
+
+// Type expressions have their own small grammar so they are parsed here instead of being
+// read as s-expressions.
+//
+// type-expr   := '[' (constraint (',' constraint)* '=>')? function ']'
+// constraint  := name+             e.g. 'Num a' or 'a Num Eq', traits start with an upper case letter
+// function    := param (',' param)* ('->' function)?
+// param       := type '..'?        only the last parameter can be variadic
+// type        := name atom*        only upper case names can take parameters
+// atom        := name | '[' function ']'
+
+#[derive(Debug, Clone)]
+enum Part {
+    Name(String),
+    Comma,
+    Arrow,
+    FatArrow,
+    Ellipsis,
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone)]
+struct TypePart {
+    part: Part,
+    si: SourceInfo,
+}
+
+pub fn scan_type(scanner: &mut Scanner) -> Result<Token, Error> {
+    let si = scanner.get_source_info();
+    let parts = scan_parts(scanner)?;
+
+    let mut parser = TypeParser {
+        parts: &parts,
+        index: 1,
+        constraints: Vec::new(),
+    };
+
+    let t = parser.parse()?;
+
+    return Ok(Token::new(TokenKind::TypeExpr(t), si));
+}
+
+// Reads everything up to the matching ']' so the scanner is past the type even if it is malformed,
+// unless the list the type is in ends first
+fn scan_parts(scanner: &mut Scanner) -> Result<Vec<TypePart>, Error> {
+    let open_si = scanner.get_source_info();
+    let mut parts = Vec::new();
+    let mut depth = 0;
+
+    loop {
+        let mut errors = Vec::new();
+        skip_whitespace(scanner, &mut errors);
+
+        if let Some(error) = errors.pop() {
+            return Err(error);
+        }
+
+        if scanner.is_at_end() {
+            return Err(Error::new(
+                "Expected ']' to end type expression".to_string(),
+                open_si,
+            ));
+        }
+
+        let si = scanner.get_source_info();
+        let c = scanner.peek();
+
+        let part = if c == '[' {
+            scanner.advance();
+            depth += 1;
+            Part::Open
+        } else if c == ']' {
+            scanner.advance();
+            depth -= 1;
+            Part::Close
+        } else if c == ',' {
+            scanner.advance();
+            Part::Comma
+        } else if scanner.match_string("->") {
+            scanner.skip(2);
+            Part::Arrow
+        } else if scanner.match_string("=>") {
+            scanner.skip(2);
+            Part::FatArrow
+        } else if scanner.match_string("..") {
+            scanner.skip(2);
+            Part::Ellipsis
+        } else if is_xid_start(c) || c == '_' {
+            Part::Name(scan_name(scanner))
+        } else {
+            // Skip the rest of the type so we don't report errors for the same type twice. A '}' or
+            // ')' means the type was never closed, it is left for the list around the type.
+            for _ in 0..depth {
+                synchronize(scanner);
+
+                if !scanner.match_char(']') {
+                    break;
+                }
+
+                scanner.advance();
+            }
+
+            return Err(Error::new(
+                format!("Unexpected character '{}' in type expression", display_char(c)),
+                si,
+            ));
+        };
+
+        parts.push(TypePart { part, si: scanner.span_from(si) });
+
+        if depth == 0 {
+            return Ok(parts);
+        }
+    }
+}
+
+fn scan_name(scanner: &mut Scanner) -> String {
+    let start = scanner.index;
+
+    while !scanner.is_at_end() {
+        let c = scanner.peek();
+
+        if (c == '-' && scanner.peek_next() == '>') || scanner.match_string("..") {
+            break;
+        }
+
+        if !is_xid_continue(c) && c != '-' && c != '.' {
+            break;
+        }
+
+        scanner.advance();
+    }
+
+    return String::from_utf8_lossy(&scanner.text[start..scanner.index]).to_string();
+}
+
+struct TypeParser<'a> {
+    parts: &'a [TypePart],
+    index: usize,
+
+    // Type variable, its traits and where it was constrained
+    constraints: Vec<(String, Vec<String>, SourceInfo)>,
+}
+
+impl<'a> TypeParser<'a> {
+    fn parse(&mut self) -> Result<Type, Error> {
+        if self.has_constraints() {
+            self.parse_constraints()?;
+        }
+
+        let t = self.parse_function(false)?;
+
+        let end = self.peek();
+        if !matches!(end.part, Part::Close) {
+            return Err(Error::new(
+                "Expected ']' to end type expression".to_string(),
+                end.si,
+            ));
+        }
+
+        for (name, _, si) in &self.constraints {
+            if !t.contains_variable(name) {
+                return Err(Error::new(
+                    format!("Constrained type variable '{name}' is not used in the type"),
+                    *si,
+                ));
+            }
+        }
+
+        return Ok(t);
+    }
+
+    fn peek(&self) -> &'a TypePart {
+        // The last part is always the closing bracket
+        &self.parts[self.index.min(self.parts.len() - 1)]
+    }
+
+    fn advance(&mut self) -> &'a TypePart {
+        let part = self.peek();
+        self.index += 1;
+        return part;
+    }
+
+    fn has_constraints(&self) -> bool {
+        let mut depth = 0;
+
+        for p in &self.parts[self.index..] {
+            match p.part {
+                Part::Open => depth += 1,
+                Part::Close if depth == 0 => return false,
+                Part::Close => depth -= 1,
+                Part::FatArrow if depth == 0 => return true,
+                _ => {},
+            }
+        }
+
+        return false;
+    }
+
+    // 'Num a, Eq a b' and 'a Num Eq, b Num' are both accepted, upper case names are traits and
+    // lower case names are the type variables they apply to.
+    fn parse_constraints(&mut self) -> Result<(), Error> {
+        loop {
+            let constraint_si = self.peek().si;
+            let mut variables = Vec::new();
+            let mut traits = Vec::new();
+
+            while let Part::Name(name) = &self.peek().part {
+                let si = self.advance().si;
+
+                if is_type_variable(name) {
+                    variables.push((name.clone(), si));
+                } else {
+                    traits.push(name.clone());
+                }
+            }
+
+            if variables.is_empty() && traits.is_empty() {
+                return Err(Error::new(
+                    "Expected a constraint like 'a Num' before '=>'".to_string(),
+                    constraint_si,
+                ));
+            }
+
+            if variables.is_empty() {
+                return Err(Error::new(
+                    "Constraint is missing the type variable it applies to".to_string(),
+                    constraint_si,
+                ));
+            }
+
+            if traits.is_empty() {
+                return Err(Error::new(
+                    "Constraint is missing a trait".to_string(),
+                    constraint_si,
+                ));
+            }
+
+            for (name, si) in variables {
+                match self.constraints.iter_mut().find(|(n, _, _)| *n == name) {
+                    Some((_, existing, _)) => {
+                        for t in &traits {
+                            if !existing.contains(t) {
+                                existing.push(t.clone());
+                            }
+                        }
+                    },
+
+                    None => self.constraints.push((name, traits.clone(), si)),
+                }
+            }
+
+            let next = self.advance();
+            match next.part {
+                Part::Comma => continue,
+                Part::FatArrow => return Ok(()),
+                _ => return Err(Error::new(
+                    "Expected ',' or '=>' after constraint".to_string(),
+                    next.si,
+                )),
+            }
+        }
+    }
+
+    fn parse_function(&mut self, is_return: bool) -> Result<Type, Error> {
+        let mut params = Vec::new();
+        let mut variadic: Option<(Type, SourceInfo)> = None;
+        let mut comma_si = None;
+
+        loop {
+            let message = if comma_si.is_none() { "Expected a type" } else { "Expected a type after ','" };
+            let param = self.parse_type(message)?;
+
+            if matches!(self.peek().part, Part::Ellipsis) {
+                let si = self.advance().si;
+
+                if variadic.is_some() {
+                    return Err(Error::new(
+                        "Function types can have at most one variadic parameter".to_string(),
+                        si,
+                    ));
+                }
+
+                variadic = Some((param, si));
+            } else if let Some((_, si)) = variadic {
+                return Err(Error::new(
+                    "Only the last parameter type can be variadic".to_string(),
+                    si,
+                ));
+            } else {
+                params.push(param);
+            }
+
+            if !matches!(self.peek().part, Part::Comma) {
+                break;
+            }
+
+            comma_si.get_or_insert(self.advance().si);
+        }
+
+        let next = self.peek();
+        match next.part {
+            Part::Arrow => {
+                self.advance();
+                let return_type = self.parse_function_return()?;
+
+                return Ok(Type::Function {
+                    params,
+                    variadic: variadic.map(|(t, _)| Box::new(t)),
+                    return_type: Box::new(return_type),
+                });
+            },
+
+            _ if variadic.is_some() => return Err(Error::new(
+                "Variadic types can only be used as function parameters".to_string(),
+                variadic.unwrap().1,
+            )),
+
+            _ if params.len() > 1 && is_return => return Err(Error::new(
+                "Function types can only have one return type, use '->' to return a function".to_string(),
+                comma_si.unwrap(),
+            )),
+
+            _ if params.len() > 1 => return Err(Error::new(
+                "Expected '->' after function parameter types".to_string(),
+                next.si,
+            )),
+
+            _ => return Ok(params.pop().unwrap()),
+        }
+    }
+
+    fn parse_function_return(&mut self) -> Result<Type, Error> {
+        return self.parse_function_or(true, |next| Error::new(
+            "Expected a return type after '->'".to_string(),
+            next.si,
+        ));
+    }
+
+    fn parse_function_or(&mut self, is_return: bool, error: impl Fn(&TypePart) -> Error) -> Result<Type, Error> {
+        let next = self.peek();
+        match next.part {
+            Part::Name(_) | Part::Open => self.parse_function(is_return),
+            _ => Err(error(next)),
+        }
+    }
+
+    fn parse_type(&mut self, message: &str) -> Result<Type, Error> {
+        let next = self.advance();
+
+        let name = match &next.part {
+            Part::Name(name) => name,
+            Part::Open => return self.parse_group(),
+
+            // Only the part before the arrow knows if it was a missing parameter or a stray comma
+            Part::Arrow if !matches!(self.parts[self.index - 2].part, Part::Comma) => return Err(Error::new(
+                "Expected parameter types before '->'".to_string(),
+                next.si,
+            )),
+
+            _ => return Err(Error::new(message.to_string(), next.si)),
+        };
+
+        let mut params = Vec::new();
+        loop {
+            let param = match &self.peek().part {
+                Part::Name(name) => {
+                    self.advance();
+                    self.named_type(name)
+                },
+
+                Part::Open => {
+                    self.advance();
+                    self.parse_group()?
+                },
+
+                _ => break,
+            };
+
+            params.push(param);
+        }
+
+        if params.is_empty() {
+            return Ok(self.named_type(name));
+        }
+
+        if is_type_variable(name) {
+            return Err(Error::new(
+                format!("Type variable '{name}' can not take type parameters"),
+                next.si,
+            ));
+        }
+
+        return Ok(Type::Complex {
+            name: name.clone(),
+            params,
+        });
+    }
+
+    // [a -> b] used as a single type
+    fn parse_group(&mut self) -> Result<Type, Error> {
+        let t = self.parse_function_or(false, |next| Error::new(
+            "Expected a type after '['".to_string(),
+            next.si,
+        ))?;
+
+        let next = self.advance();
+        if !matches!(next.part, Part::Close) {
+            return Err(Error::new(
+                "Expected ']' to end nested type".to_string(),
+                next.si,
+            ));
+        }
+
+        return Ok(t);
+    }
+
+    fn named_type(&self, name: &str) -> Type {
+        if !is_type_variable(name) {
+            return Type::Simple(name.to_string());
+        }
+
+        let traits = self.constraints.iter()
+            .find(|(n, _, _)| n == name)
+            .map_or(Vec::new(), |(_, traits, _)| traits.clone());
+
+        return Type::Generic {
+            name: name.to_string(),
+            traits,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize;
+
+    // Types can't be written at the top level so they are put in a list
+    fn parse(text: &str) -> Type {
+        let mut interner = Interner::new();
+        let tokens = tokenize(format!("{{f {text}}}").as_bytes(), FileId(0), &mut interner).unwrap();
+
+        return match &tokens[0].sexpr().unwrap()[1].kind {
+            TokenKind::TypeExpr(t) => t.clone(),
+            kind => panic!("expected a type expression, got {kind:?}"),
+        };
+    }
+
+    // Column in text and message of the only error
+    fn error(text: &str) -> (i64, String) {
+        let mut interner = Interner::new();
+        let errors = tokenize(format!("{{f {text}}}").as_bytes(), FileId(0), &mut interner).unwrap_err();
+
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        return (errors[0].si.column - 3, errors[0].message.clone());
+    }
+
+    fn simple(name: &str) -> Type {
+        Type::Simple(name.to_string())
+    }
+
+    fn generic(name: &str, traits: &[&str]) -> Type {
+        Type::Generic {
+            name: name.to_string(),
+            traits: traits.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn function(params: Vec<Type>, variadic: Option<Type>, return_type: Type) -> Type {
+        Type::Function {
+            params,
+            variadic: variadic.map(Box::new),
+            return_type: Box::new(return_type),
+        }
+    }
+
+    #[test]
+    fn types() {
+        assert_eq!(parse("[Int]"), simple("Int"));
+        assert_eq!(parse("[List String]"), Type::Complex {
+            name: "List".to_string(),
+            params: vec![simple("String")],
+        });
+
+        let (a, b) = (generic("a", &[]), generic("b", &[]));
+
+        let (int, string, unit) = (simple("Int"), simple("String"), simple("Unit"));
+
+        assert_eq!(parse("[Int, Int -> Int]"), function(vec![int.clone(), int.clone()], None, int));
+        assert_eq!(parse("[String, a.. -> Unit]"), function(vec![string], Some(a.clone()), unit));
+
+        // Arrows are right associative and brackets group a function type into one parameter
+        let a_to_b = function(vec![a.clone()], None, b.clone());
+        assert_eq!(parse("[a -> [a -> b] -> b]"), function(vec![a], None, function(vec![a_to_b], None, b)));
+    }
+
+    #[test]
+    fn constraints() {
+        let expected = function(
+            vec![generic("a", &["Num", "Eq"]), generic("b", &["Num"])],
+            None,
+            generic("c", &[]),
+        );
+
+        assert_eq!(parse("[Num a Eq, b Num => a, b -> c]"), expected);
+        assert_eq!(parse("[Num a, Eq a, Num b => a, b -> c]"), expected);
+    }
+
+    #[test]
+    fn type_errors() {
+        let cases = [
+            ("[Int, -> Int]", 7, "Expected a type after ','"),
+            ("[-> Int]", 2, "Expected parameter types before '->'"),
+            ("[Int ->]", 8, "Expected a return type after '->'"),
+            ("[Int, Int]", 10, "Expected '->' after function parameter types"),
+            ("[Int -> Int, Int]", 12,
+                "Function types can only have one return type, use '->' to return a function"),
+            ("[a.., b -> c]", 3, "Only the last parameter type can be variadic"),
+            ("[a.., b.. -> c]", 8, "Function types can have at most one variadic parameter"),
+            ("[a..]", 3, "Variadic types can only be used as function parameters"),
+            ("[=> a]", 2, "Expected a constraint like 'a Num' before '=>'"),
+            ("[Num => a]", 2, "Constraint is missing the type variable it applies to"),
+            ("[a => a]", 2, "Constraint is missing a trait"),
+            ("[Num a => b]", 6, "Constrained type variable 'a' is not used in the type"),
+            ("[a Int]", 2, "Type variable 'a' can not take type parameters"),
+            ("[[Int => a] -> a]", 7, "Expected ']' to end nested type"),
+            ("[Int $]", 6, "Unexpected character '$' in type expression"),
+
+            // The '}' of the list around the type
+            ("[Int", 5, "Unexpected character '}' in type expression"),
+        ];
+
+        for (text, column, message) in cases {
+            assert_eq!(error(text), (column, message.to_string()), "{text}");
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::SourceInfo;
+use super::{SourceInfo, SourceMap};
 
 #[derive(Clone, Debug)]
 pub struct Error {
@@ -18,4 +18,29 @@ impl Error {
     pub fn to_string(&self, file_name: &str) -> String {
         format!("ERROR::{file_name}({}:{}) {}", self.si.line, self.si.column, &self.message)
     }
+
+    // Names the file the error is in and underlines the span on the first line of it
+    pub fn report(&self, sources: &SourceMap) -> String {
+        let mut result = self.to_string(sources.get_name(self.si.file));
+
+        let line = match sources.get_file(self.si.file).and_then(|f| f.line_text(self.si.line)) {
+            Some(line) => String::from_utf8_lossy(line).trim_end().to_string(),
+            None => return result,
+        };
+
+        let start = (self.si.column - 1).max(0) as usize;
+        let end = if self.si.end_line == self.si.line {
+            (self.si.end_column - 1).max(0) as usize
+        } else {
+            line.chars().count()
+        };
+
+        let gutter = self.si.line.to_string();
+        let padding = " ".repeat(gutter.len());
+
+        result.push_str(&format!("\n {gutter} | {line}"));
+        result.push_str(&format!("\n {padding} | {}{}", " ".repeat(start), "^".repeat(end.saturating_sub(start).max(1))));
+
+        return result;
+    }
 }
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+// Interned name, comparing and copying symbols is as cheap as comparing integers.
+// A symbol only means something together with the interner that created it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+// Keywords are interned first by every interner so they have the same symbol everywhere and
+// can be checked without an interner.
+const KEYWORDS: [&str; 20] = [
+    "let",
+    "fun",
+    "function",
+    "procedure",
+    "struct",
+    "enum",
+    "quote",
+    "quasiquote",
+    "unquote",
+    "unquote-splicing",
+    "list",
+    "where",
+    "import",
+    "import-qualified",
+    "include",
+    "exclude",
+    "infixl",
+    "infixr",
+    "infix",
+    "macro",
+];
+
+impl Symbol {
+    pub const LET: Symbol = Symbol(0);
+    pub const FUN: Symbol = Symbol(1);
+    pub const FUNCTION: Symbol = Symbol(2);
+    pub const PROCEDURE: Symbol = Symbol(3);
+    pub const STRUCT: Symbol = Symbol(4);
+    pub const ENUM: Symbol = Symbol(5);
+    pub const QUOTE: Symbol = Symbol(6);
+    pub const QUASIQUOTE: Symbol = Symbol(7);
+    pub const UNQUOTE: Symbol = Symbol(8);
+    pub const UNQUOTE_SPLICING: Symbol = Symbol(9);
+    pub const LIST: Symbol = Symbol(10);
+    pub const WHERE: Symbol = Symbol(11);
+    pub const IMPORT: Symbol = Symbol(12);
+    pub const IMPORT_QUALIFIED: Symbol = Symbol(13);
+    pub const INCLUDE: Symbol = Symbol(14);
+    pub const EXCLUDE: Symbol = Symbol(15);
+    pub const INFIXL: Symbol = Symbol(16);
+    pub const INFIXR: Symbol = Symbol(17);
+    pub const INFIX: Symbol = Symbol(18);
+    pub const MACRO: Symbol = Symbol(19);
+}
+
+#[derive(Debug)]
+pub struct Interner {
+    lookup: HashMap<Box<str>, Symbol>,
+    names: Vec<Box<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        let mut interner = Self {
+            lookup: HashMap::new(),
+            names: Vec::new(),
+        };
+
+        for keyword in KEYWORDS {
+            interner.intern(keyword);
+        }
+
+        return interner;
+    }
+
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.lookup.get(name) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.into());
+        self.lookup.insert(name.into(), symbol);
+
+        return symbol;
+    }
+
+    // Symbol of a name that has already been interned
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.lookup.get(name).copied()
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+}
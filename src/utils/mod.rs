@@ -5,3 +5,6 @@ pub use error::*;
 
 mod source_info;
 pub use source_info::*;
+
+mod interner;
+pub use interner::*;
@@ -1,16 +1,130 @@
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(pub usize);
+
+// A span in a source file, the end is exclusive.
+// Positions that point at a single place in the file have the same start and end.
 #[derive(Default, Debug, Clone, Copy)]
 pub struct SourceInfo {
+    pub file: FileId,
+
     pub line: i64,
     pub column: i64,
     pub index: usize,
+
+    pub end_line: i64,
+    pub end_column: i64,
+    pub end_index: usize,
 }
 
 impl SourceInfo {
     pub fn new(
+        file: FileId,
         line: i64,
         column: i64,
         index: usize,
     ) -> Self {
-        Self { line, column, index }
+        Self {
+            file,
+            line, column, index,
+            end_line: line, end_column: column, end_index: index,
+        }
+    }
+
+    // Span from the start of self to the end of other
+    pub fn to(self, other: SourceInfo) -> Self {
+        Self {
+            end_line: other.end_line,
+            end_column: other.end_column,
+            end_index: other.end_index,
+            ..self
+        }
+    }
+
+    // Empty span right after self, used for things that are implied by the source like a missing type
+    pub fn after(self) -> Self {
+        Self::new(self.file, self.end_line, self.end_column, self.end_index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.end_index - self.index
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: Vec<u8>,
+}
+
+impl SourceFile {
+    pub fn line_text(&self, line: i64) -> Option<&[u8]> {
+        if line < 1 {
+            return None;
+        }
+
+        self.text.split(|&b| b == b'\n').nth(line as usize - 1)
+    }
+
+    // Editor protocols like LSP count positions in UTF-16 code units instead of characters
+    pub fn utf16_offset(&self, index: usize) -> usize {
+        let index = index.min(self.text.len());
+        return String::from_utf8_lossy(&self.text[..index]).encode_utf16().count();
+    }
+
+    // 1 based like SourceInfo::column
+    pub fn utf16_column(&self, index: usize) -> i64 {
+        let index = index.min(self.text.len());
+        let line_start = self.text[..index].iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+
+        return String::from_utf8_lossy(&self.text[line_start..index]).encode_utf16().count() as i64 + 1;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    pub fn add_file(&mut self, name: String, text: Vec<u8>) -> FileId {
+        self.files.push(SourceFile { name, text });
+        return FileId(self.files.len() - 1);
+    }
+
+    pub fn get_file(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.0)
+    }
+
+    pub fn get_name(&self, id: FileId) -> &str {
+        self.get_file(id).map_or("<unknown>", |f| &f.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_positions() {
+        let file = SourceFile {
+            name: "test.xl".to_string(),
+            text: "a😀b\nλc".as_bytes().to_vec(),
+        };
+
+        // The emoji is 4 bytes of UTF-8 and 2 code units of UTF-16
+        assert_eq!(file.utf16_offset(5), 3);
+        assert_eq!(file.utf16_column(5), 4);
+
+        // Columns start over on every line
+        assert_eq!(file.utf16_offset(9), 6);
+        assert_eq!(file.utf16_column(9), 2);
     }
 }